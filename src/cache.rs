@@ -0,0 +1,133 @@
+use rustc_serialize::Encodable;
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use toml;
+use toml::{Encoder, Parser, Value};
+
+///
+/// A directory walked into during a previous scan:
+/// `modified_secs` and `modified_nanos`: The last modification time of the directory
+/// `kind`: The kind of git repository of the directory, if it is one
+/// `subdirectories`: The names of its subdirectories, or None if they have not been listed
+/// `links`: The names of its symbolic links, which may point to directories
+/// `ignore_file`: True if the directory contains a .gyroignore file
+///
+#[derive(Clone, RustcDecodable, RustcEncodable)]
+pub struct CachedDirectory {
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+    pub kind: Option<String>,
+    pub subdirectories: Option<Vec<String>>,
+    pub links: Vec<String>,
+    pub ignore_file: bool,
+}
+
+impl CachedDirectory {
+    ///
+    /// The function to instanciate a new CachedDirectory structure, from the metadata of the
+    /// directory
+    ///
+    pub fn new(metadata: &Metadata) -> Self {
+        let (modified_secs, modified_nanos) = get_modification_time(metadata);
+        CachedDirectory {
+            modified_secs: modified_secs,
+            modified_nanos: modified_nanos,
+            kind: None,
+            subdirectories: None,
+            links: Vec::new(),
+            ignore_file: false,
+        }
+    }
+
+    ///
+    /// This method returns a boolean - true if the directory has not been modified since it has
+    /// been cached, else false
+    ///
+    pub fn is_up_to_date(&self, metadata: &Metadata) -> bool {
+        get_modification_time(metadata) == (self.modified_secs, self.modified_nanos)
+    }
+}
+
+///
+/// The root commit of a git repository found during a previous scan:
+/// `head`: The commit id of HEAD when the root commit has been found
+/// `root_commit`: The id of the root commit of HEAD, following first parents
+///
+/// The root commit is found again only if HEAD has changed since it has been cached.
+///
+#[derive(Clone, RustcDecodable, RustcEncodable)]
+pub struct CachedRootCommit {
+    pub head: String,
+    pub root_commit: String,
+}
+
+///
+/// Function to get the modification time of a file, in seconds and nanoseconds since the Unix
+/// epoch.
+///
+fn get_modification_time(metadata: &Metadata) -> (u64, u32) {
+    match metadata.modified().ok().and_then(|modified| modified.duration_since(UNIX_EPOCH).ok()) {
+        Some(duration) => (duration.as_secs(), duration.subsec_nanos()),
+        None => (0, 0),
+    }
+}
+
+///
+/// The scan cache, stored next to the configuration file, that contains each directory walked
+/// into during the previous scans, and the root commit of each git repository whose identity has
+/// been read - both indexed by their path.
+///
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct ScanCache {
+    pub directories: HashMap<String, CachedDirectory>,
+    pub root_commits: HashMap<String, CachedRootCommit>,
+}
+
+impl ScanCache {
+    ///
+    /// The function to instanciate an empty ScanCache structure
+    ///
+    pub fn new() -> Self {
+        ScanCache {
+            directories: HashMap::new(),
+            root_commits: HashMap::new(),
+        }
+    }
+
+    ///
+    /// This function returns an Option type, that contains the scan cache stored in the given
+    /// file, or None if the file cannot be read or parsed
+    ///
+    pub fn load(path: &Path) -> Option<Self> {
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => {
+                if file.read_to_string(&mut content).is_err() {
+                    return None;
+                }
+            }
+            Err(_) => return None,
+        }
+        let table = match Parser::new(&content).parse() {
+            Some(table) => table,
+            None => return None,
+        };
+        toml::decode::<ScanCache>(Value::Table(table))
+    }
+
+    ///
+    /// This method saves the scan cache into a file (given by its path), and returns a Result
+    /// type
+    ///
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut encoder = Encoder::new();
+        if let Err(error) = self.encode(&mut encoder) {
+            return Err(Error::new(ErrorKind::InvalidData, format!("{:?}", error)));
+        }
+        let mut cache_file = File::create(path)?;
+        cache_file.write_all(toml::encode_str(&Value::Table(encoder.toml)).as_bytes())
+    }
+}
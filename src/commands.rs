@@ -1,5 +1,5 @@
 use clap::{App, Arg, ArgMatches, SubCommand};
-use libgyro::{IGNORED_ENTRY_NAME, WATCHED_ENTRY_NAME};
+use libgyro::{IGNORED_ENTRY_NAME, MISSING_ENTRY_NAME, WATCHED_ENTRY_NAME};
 
 // Program relative
 
@@ -24,17 +24,74 @@ pub static OVERRIDE_SUBCMD_CATEGORY_FLAG: &'static str = "category";
 
 /// Move subcommand
 pub static REPO_SUBCMD: &'static str = "repo";
+pub static REPO_SUBCMD_ALIAS_FLAG: &'static str = "alias";
 pub static REPO_SUBCMD_MOVE_FLAG: &'static str = "move";
 pub static REPO_SUBCMD_NAME_FLAG: &'static str = "name";
 
 /// Scan subcommand
 pub static SCAN_SUBCMD: &'static str = "scan";
 pub static SCAN_SUBCMD_DIFF_FLAG: &'static str = "diff";
+pub static SCAN_SUBCMD_FULL_FLAG: &'static str = "full";
+pub static SCAN_SUBCMD_HIDDEN_FLAG: &'static str = "hidden";
+pub static SCAN_SUBCMD_INCLUDE_FLAG: &'static str = "include";
+pub static SCAN_SUBCMD_JOBS_FLAG: &'static str = "jobs";
+static SCAN_SUBCMD_JOBS_FLAG_SHORT: &'static str = "j";
+pub static SCAN_SUBCMD_MAX_DEPTH_FLAG: &'static str = "max-depth";
+pub static SCAN_SUBCMD_MIN_DEPTH_FLAG: &'static str = "min-depth";
+pub static SCAN_SUBCMD_NESTED_FLAG: &'static str = "nested";
+pub static SCAN_SUBCMD_ONE_FILE_SYSTEM_FLAG: &'static str = "one-file-system";
+pub static SCAN_SUBCMD_PRUNE_FLAG: &'static str = "prune";
+pub static SCAN_SUBCMD_PRUNE_REMOVE_VALUE: &'static str = "remove";
+pub static SCAN_SUBCMD_ROOT_FLAG: &'static str = "root";
+pub static SCAN_SUBCMD_VERBOSE_FLAG: &'static str = "verbose";
+static SCAN_SUBCMD_VERBOSE_FLAG_SHORT: &'static str = "v";
+pub static SCAN_SUBCMD_WATCH_FLAG: &'static str = "watch";
+
+/// Stashes subcommand
+pub static STASHES_SUBCMD: &'static str = "stashes";
+pub static STASHES_SUBCMD_OLDER_THAN_FLAG: &'static str = "older-than";
 
 /// Status subcommand
 pub static STATUS_SUBCMD: &'static str = "status";
+pub static STATUS_SUBCMD_BEHIND_FLAG: &'static str = "behind";
 pub static STATUS_SUBCMD_CLEAN_FLAG: &'static str = "clean";
 pub static STATUS_SUBCMD_DIRTY_FLAG: &'static str = "dirty";
+pub static STATUS_SUBCMD_IN_PROGRESS_FLAG: &'static str = "in-progress";
+pub static STATUS_SUBCMD_UNPUSHED_FLAG: &'static str = "unpushed";
+
+/// Function to parse an age given as argument, like `30d`: a number of hours (`h`), days (`d`),
+/// weeks (`w`), months (`mo`) or years (`y`) - days, without unit.
+/// This function returns the age in seconds, or None if it is not valid.
+pub fn parse_age(age: &str) -> Option<i64> {
+    let (count, unit_seconds) = match age.chars().last() {
+        Some('o') if age.ends_with("mo") => (&age[..age.len() - 2], 30 * 24 * 3600),
+        Some('h') => (&age[..age.len() - 1], 3600),
+        Some('d') => (&age[..age.len() - 1], 24 * 3600),
+        Some('w') => (&age[..age.len() - 1], 7 * 24 * 3600),
+        Some('y') => (&age[..age.len() - 1], 365 * 24 * 3600),
+        _ => (age, 24 * 3600),
+    };
+    count.parse::<u32>().ok().map(|count| count as i64 * unit_seconds)
+}
+
+/// Function to validate an age given as argument, like `30d`.
+fn is_age(age: String) -> Result<(), String> {
+    match parse_age(&age) {
+        Some(_) => Ok(()),
+        None => {
+            Err(String::from("The age must be a number of hours, days, weeks, months or years, \
+                              like 12h, 30d, 2w, 6mo or 1y"))
+        }
+    }
+}
+
+/// Function to validate a depth given as argument: a non-negative integer.
+fn is_depth(depth: String) -> Result<(), String> {
+    match depth.parse::<usize>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(String::from("The depth must be a non-negative integer")),
+    }
+}
 
 /// Function to get arguments of the program.
 /// This function returns an ArgMatches type.
@@ -66,6 +123,16 @@ pub fn get_program_args<'a>() -> ArgMatches<'a> {
                 .help("The local git repository to play with")
                 .index(1)
                 .required(true))
+            .arg(Arg::with_name(REPO_SUBCMD_ALIAS_FLAG)
+                .help("Give another name to the given repository")
+                .long(REPO_SUBCMD_ALIAS_FLAG)
+                .takes_value(true)
+                .conflicts_with(REPO_SUBCMD_MOVE_FLAG)
+                .validator(|alias| if alias.trim().is_empty() {
+                    Err(String::from("The alias of a repository cannot be empty"))
+                } else {
+                    Ok(())
+                }))
             .arg(Arg::with_name(REPO_SUBCMD_MOVE_FLAG)
                 .help("Move the given repository to the other repositories container (watched, \
                        or ignored)")
@@ -75,16 +142,134 @@ pub fn get_program_args<'a>() -> ArgMatches<'a> {
             .about("Scan your hard disk to find git repositories")
             .arg(Arg::with_name(SCAN_SUBCMD_DIFF_FLAG)
                 .help("Print new git repositories from your hard disk")
-                .long(SCAN_SUBCMD_DIFF_FLAG)))
+                .long(SCAN_SUBCMD_DIFF_FLAG))
+            .arg(Arg::with_name(SCAN_SUBCMD_FULL_FLAG)
+                .help("Walk into every directory, even the ones that have not changed since the \
+                       last scan")
+                .long(SCAN_SUBCMD_FULL_FLAG))
+            .arg(Arg::with_name(SCAN_SUBCMD_HIDDEN_FLAG)
+                .help("Keep the git repositories found in hidden directories")
+                .long(SCAN_SUBCMD_HIDDEN_FLAG))
+            .arg(Arg::with_name(SCAN_SUBCMD_INCLUDE_FLAG)
+                .help("Only find the git repositories matching the given glob pattern, like \
+                       '~/code/*/*' (can be repeated)")
+                .long(SCAN_SUBCMD_INCLUDE_FLAG)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+            .arg(Arg::with_name(SCAN_SUBCMD_JOBS_FLAG)
+                .help("The number of threads that scan your hard disk")
+                .short(SCAN_SUBCMD_JOBS_FLAG_SHORT)
+                .long(SCAN_SUBCMD_JOBS_FLAG)
+                .takes_value(true)
+                .validator(|jobs| match jobs.parse::<usize>() {
+                    Ok(jobs) if jobs > 0 => Ok(()),
+                    _ => Err(String::from("The number of threads must be a positive integer")),
+                }))
+            .arg(Arg::with_name(SCAN_SUBCMD_MAX_DEPTH_FLAG)
+                .help("Never walk into directories deeper than the given depth, from the scanned \
+                       directory")
+                .long(SCAN_SUBCMD_MAX_DEPTH_FLAG)
+                .takes_value(true)
+                .validator(is_depth))
+            .arg(Arg::with_name(SCAN_SUBCMD_MIN_DEPTH_FLAG)
+                .help("Ignore the git repositories less deep than the given depth, from the \
+                       scanned directory")
+                .long(SCAN_SUBCMD_MIN_DEPTH_FLAG)
+                .takes_value(true)
+                .validator(is_depth))
+            .arg(Arg::with_name(SCAN_SUBCMD_NESTED_FLAG)
+                .help("Walk into git repositories to find nested ones, that are not submodules")
+                .long(SCAN_SUBCMD_NESTED_FLAG))
+            .arg(Arg::with_name(SCAN_SUBCMD_ONE_FILE_SYSTEM_FLAG)
+                .help("Never walk into another file system than the one of the scanned directory")
+                .long(SCAN_SUBCMD_ONE_FILE_SYSTEM_FLAG))
+            .arg(Arg::with_name(SCAN_SUBCMD_PRUNE_FLAG)
+                .help("Remove the repositories that are not on your hard disk anymore, or move \
+                       them to the missing category")
+                .long(SCAN_SUBCMD_PRUNE_FLAG)
+                .takes_value(true)
+                .possible_values(&[SCAN_SUBCMD_PRUNE_REMOVE_VALUE, MISSING_ENTRY_NAME])
+                .conflicts_with(SCAN_SUBCMD_DIFF_FLAG))
+            .arg(Arg::with_name(SCAN_SUBCMD_ROOT_FLAG)
+                .help("Scan the given directory instead of the ones from your configuration file \
+                       (can be repeated)")
+                .long(SCAN_SUBCMD_ROOT_FLAG)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+            .arg(Arg::with_name(SCAN_SUBCMD_VERBOSE_FLAG)
+                .help("List each error encountered during the scan")
+                .short(SCAN_SUBCMD_VERBOSE_FLAG_SHORT)
+                .long(SCAN_SUBCMD_VERBOSE_FLAG))
+            .arg(Arg::with_name(SCAN_SUBCMD_WATCH_FLAG)
+                .help("After the scan, keep watching the scanned directories, and update your \
+                       configuration file each time a git repository appears or disappears")
+                .long(SCAN_SUBCMD_WATCH_FLAG)
+                .conflicts_with(SCAN_SUBCMD_DIFF_FLAG)))
+        .subcommand(SubCommand::with_name(STASHES_SUBCMD)
+            .author("A. Carette <antonin@carette.xyz>")
+            .about("List the stashes of watched git repositories")
+            .arg(Arg::with_name(STASHES_SUBCMD_OLDER_THAN_FLAG)
+                .help("Get only the stashes older than the given age, like 30d (hours, days, \
+                       weeks, months or years)")
+                .long(STASHES_SUBCMD_OLDER_THAN_FLAG)
+                .takes_value(true)
+                .validator(is_age)))
         .subcommand(SubCommand::with_name(STATUS_SUBCMD)
             .author("A. Carette <antonin@carette.xyz>")
             .about("Get the status of watched git repositories")
+            .arg(Arg::with_name(STATUS_SUBCMD_BEHIND_FLAG)
+                .help("Get only projects whose current branch is behind its upstream")
+                .long(STATUS_SUBCMD_BEHIND_FLAG))
             .arg(Arg::with_name(STATUS_SUBCMD_CLEAN_FLAG)
                 .help("Get only clean projects")
                 .long(STATUS_SUBCMD_CLEAN_FLAG)
                 .conflicts_with(STATUS_SUBCMD_DIRTY_FLAG))
             .arg(Arg::with_name(STATUS_SUBCMD_DIRTY_FLAG)
                 .help("Get only dirty projects")
-                .long(STATUS_SUBCMD_DIRTY_FLAG)))
+                .long(STATUS_SUBCMD_DIRTY_FLAG))
+            .arg(Arg::with_name(STATUS_SUBCMD_IN_PROGRESS_FLAG)
+                .help("Get only projects with an operation in progress, like a rebase or a merge")
+                .long(STATUS_SUBCMD_IN_PROGRESS_FLAG))
+            .arg(Arg::with_name(STATUS_SUBCMD_UNPUSHED_FLAG)
+                .help("Get only projects whose current branch has commits that are not on its \
+                       upstream")
+                .long(STATUS_SUBCMD_UNPUSHED_FLAG)))
         .get_matches()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_age;
+
+    #[test]
+    fn age_without_unit_is_in_days() {
+        assert_eq!(parse_age("30"), Some(30 * 24 * 3600));
+        assert_eq!(parse_age("0"), Some(0));
+    }
+
+    #[test]
+    fn age_with_unit() {
+        assert_eq!(parse_age("12h"), Some(12 * 3600));
+        assert_eq!(parse_age("30d"), Some(30 * 24 * 3600));
+        assert_eq!(parse_age("2w"), Some(2 * 7 * 24 * 3600));
+        assert_eq!(parse_age("6mo"), Some(6 * 30 * 24 * 3600));
+        assert_eq!(parse_age("1y"), Some(365 * 24 * 3600));
+    }
+
+    #[test]
+    fn age_without_count_is_invalid() {
+        assert_eq!(parse_age(""), None);
+        assert_eq!(parse_age("d"), None);
+        assert_eq!(parse_age("mo"), None);
+    }
+
+    #[test]
+    fn age_with_unknown_unit_is_invalid() {
+        assert_eq!(parse_age("6m"), None);
+        assert_eq!(parse_age("3x"), None);
+        assert_eq!(parse_age("-3d"), None);
+        assert_eq!(parse_age("1.5d"), None);
+    }
+}
@@ -1,10 +1,14 @@
-use {ConfigurationContent, ConfigurationFile, GROUPS_ENTRY_NAME, IGNORED_ENTRY_NAME,
-     WATCHED_ENTRY_NAME};
+use {ConfigurationContent, ConfigurationFile, BODY_ENTRY_NAME, GROUPS_ENTRY_NAME,
+     IGNORED_ENTRY_NAME, MISSING_ENTRY_NAME, WATCHED_ENTRY_NAME};
 use chrono::offset::utc::UTC;
+use rules::CategoryRule;
 use rustc_serialize::Encodable;
+use scan::canonicalize_path;
 use std::error::Error;
 use std::fmt;
+use std::path::Path;
 use std::result;
+use std::str::FromStr;
 use toml;
 use toml::Value;
 
@@ -13,12 +17,27 @@ use toml::Value;
 /// `name`: The name of git repository
 /// `path`: The local path of the git repository parent
 /// `updated`: The last time that informations have been updated from the given repository
+/// `parent`: The local path of the git repository that contains this one, if any
+/// `kind`: The kind of the git repository (`normal`, `bare`, `worktree` or `submodule`)
+/// `aliases`: The other local paths of the git repository, through symbolic links - `path` is
+/// its primary path
+/// `identity`: The identity of the git repository, that does not change if it is moved (its root
+/// commit id and its remote url)
+/// `group`: The name of the group of the git repository, for entries of the `groups` category
+/// `category`: The category of the git repository before it has been moved to the `missing` one,
+/// for entries of the `missing` category
 ///
 #[derive(RustcDecodable, RustcEncodable)]
 pub struct Entry {
     pub name: String,
     pub path: String,
     pub updated: String,
+    pub parent: Option<String>,
+    pub kind: Option<String>,
+    pub aliases: Option<Vec<String>>,
+    pub identity: Option<String>,
+    pub group: Option<String>,
+    pub category: Option<String>,
 }
 
 impl Entry {
@@ -32,6 +51,12 @@ impl Entry {
             name: String::from(name),
             path: String::from(path),
             updated: UTC::now().to_rfc2822(),
+            parent: None,
+            kind: None,
+            aliases: None,
+            identity: None,
+            group: None,
+            category: None,
         }
     }
 
@@ -43,6 +68,175 @@ impl Entry {
     }
 }
 
+///
+/// The body of the configuration file, corresponding to the `config` section:
+/// `store`: The default category of new git repositories
+/// `roots`: The directories to scan in order to find git repositories (the user home directory if
+/// empty)
+/// `exclude`: The gitignore-style patterns of directories to never scan, relative to each root
+/// `nested`: Walk into git repositories to find nested ones (false if empty)
+/// `jobs`: The number of threads that scan directories (1 if empty)
+/// `hidden`: Keep git repositories found in hidden directories (false if empty)
+/// `hidden_allow`: The patterns of hidden directories whose git repositories are always kept
+/// `hidden_deny`: The patterns of hidden directories whose git repositories are never kept
+/// `one_file_system`: Never walk into another file system than the one of a root (false if empty)
+/// `skip_mount_types`: The types of file systems to never walk into, like `fuse.sshfs`
+/// `skip_mounts`: The mount points to never walk into
+/// `min_depth`: The minimal depth of git repositories, from each root (0 if empty)
+/// `max_depth`: The maximal depth of git repositories, from each root (unlimited if empty)
+/// `include`: The glob patterns of git repositories to find, like `~/code/*/*` (every git
+/// repository if empty)
+/// `rules`: The ordered rules that decide the category of new git repositories (`store` if none
+/// matches)
+///
+#[derive(RustcDecodable)]
+pub struct Body {
+    pub store: String,
+    pub roots: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub nested: Option<bool>,
+    pub jobs: Option<usize>,
+    pub hidden: Option<bool>,
+    pub hidden_allow: Option<Vec<String>>,
+    pub hidden_deny: Option<Vec<String>>,
+    pub one_file_system: Option<bool>,
+    pub skip_mount_types: Option<Vec<String>>,
+    pub skip_mounts: Option<Vec<String>>,
+    pub min_depth: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub include: Option<Vec<String>>,
+    pub rules: Option<Vec<CategoryRule>>,
+}
+
+impl Body {
+    ///
+    /// A method to get the directories to scan, as written in the configuration file
+    ///
+    pub fn get_roots(&self) -> Vec<String> {
+        match self.roots {
+            Some(ref roots) => roots.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// A method to get the exclusion patterns, as written in the configuration file
+    ///
+    pub fn get_exclude_patterns(&self) -> Vec<String> {
+        match self.exclude {
+            Some(ref exclude) => exclude.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// A method to know if nested git repositories have to be found
+    ///
+    pub fn get_nested(&self) -> bool {
+        self.nested.unwrap_or(false)
+    }
+
+    ///
+    /// A method to get the number of threads that scan directories
+    ///
+    pub fn get_jobs(&self) -> usize {
+        self.jobs.unwrap_or(1)
+    }
+
+    ///
+    /// A method to know if git repositories found in hidden directories have to be kept
+    ///
+    pub fn get_hidden(&self) -> bool {
+        self.hidden.unwrap_or(false)
+    }
+
+    ///
+    /// A method to get the patterns of hidden directories whose git repositories are always kept
+    ///
+    pub fn get_hidden_allow_patterns(&self) -> Vec<String> {
+        match self.hidden_allow {
+            Some(ref hidden_allow) => hidden_allow.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// A method to get the patterns of hidden directories whose git repositories are never kept
+    ///
+    pub fn get_hidden_deny_patterns(&self) -> Vec<String> {
+        match self.hidden_deny {
+            Some(ref hidden_deny) => hidden_deny.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// A method to know if the scan has to stay on the file system of each root
+    ///
+    pub fn get_one_file_system(&self) -> bool {
+        self.one_file_system.unwrap_or(false)
+    }
+
+    ///
+    /// A method to get the types of file systems to never walk into
+    ///
+    pub fn get_skip_mount_types(&self) -> Vec<String> {
+        match self.skip_mount_types {
+            Some(ref skip_mount_types) => skip_mount_types.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// A method to get the mount points to never walk into, as written in the configuration file
+    ///
+    pub fn get_skip_mounts(&self) -> Vec<String> {
+        match self.skip_mounts {
+            Some(ref skip_mounts) => skip_mounts.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// A method to get the patterns of git repositories to find, as written in the configuration
+    /// file
+    ///
+    pub fn get_include_patterns(&self) -> Vec<String> {
+        match self.include {
+            Some(ref include) => include.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// A method to get the rules that decide the category of new git repositories
+    ///
+    pub fn get_rules(&self) -> Vec<CategoryRule> {
+        match self.rules {
+            Some(ref rules) => rules.clone(),
+            None => Vec::new(),
+        }
+    }
+}
+
+///
+/// Function to get a key for a new entry, that is not used by another one.
+///
+/// The key is the name of the git repository directory, prefixed by as many parent directory
+/// names as needed to be unique - for example, `api` then `oss/api`.
+/// If every suffix of the path is used, the whole path is returned.
+///
+pub fn get_entry_key(path: &str, used_keys: &[String]) -> String {
+    let segments = path.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<&str>>();
+    for length in 1..segments.len() + 1 {
+        let key = segments[segments.len() - length..].join("/");
+        if !used_keys.contains(&key) {
+            return key;
+        }
+    }
+    String::from(path)
+}
+
 ///
 /// A custom type that return a T type, or a ConfigureContentError error
 ///
@@ -52,15 +246,48 @@ type Result<T> = result::Result<T, ConfigureContentError>;
 /// The specific category to add or remove an entry from the configuration file:
 /// `Groups` is corresponding to the `groups` array title
 /// `Ignored` is corresponding to the `ignored` array title
+/// `Missing` is corresponding to the `missing` array title
 /// `Watched` is corresponding to the `watched` array title
 ///
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum EntryCategory {
     Groups,
     Ignored,
+    Missing,
     Watched,
 }
 
+impl EntryCategory {
+    ///
+    /// This method returns the name of the category, as written in the configuration file
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            EntryCategory::Groups => GROUPS_ENTRY_NAME,
+            EntryCategory::Ignored => IGNORED_ENTRY_NAME,
+            EntryCategory::Missing => MISSING_ENTRY_NAME,
+            EntryCategory::Watched => WATCHED_ENTRY_NAME,
+        }
+    }
+}
+
+impl FromStr for EntryCategory {
+    type Err = String;
+
+    ///
+    /// Function that returns the category corresponding to its name
+    ///
+    fn from_str(category: &str) -> result::Result<Self, Self::Err> {
+        match category {
+            name if name == GROUPS_ENTRY_NAME => Ok(EntryCategory::Groups),
+            name if name == IGNORED_ENTRY_NAME => Ok(EntryCategory::Ignored),
+            name if name == MISSING_ENTRY_NAME => Ok(EntryCategory::Missing),
+            name if name == WATCHED_ENTRY_NAME => Ok(EntryCategory::Watched),
+            _ => Err(format!("Unknown category '{}'", category)),
+        }
+    }
+}
+
 ///
 /// Enum that contain possible error flags for ConfigureContent:
 /// `BadPosition` is corresponding to an error when transfering an entry between two categories
@@ -97,11 +324,26 @@ impl fmt::Display for ConfigureContentError {
 }
 
 pub trait ConfigureContent {
+    ///
+    /// Method to get the body of the configuration file
+    ///
+    fn get_body(&self) -> Result<Body>;
+
     ///
     /// Method to get the entry path, from a key and a category
     ///
     fn get_entry_path(&self, key: &str, category: &EntryCategory) -> String;
 
+    ///
+    /// Method to get the entry represented by a string key, in a given category
+    ///
+    fn get_entry(&self, key: &str, category: &EntryCategory) -> Result<Entry>;
+
+    ///
+    /// Method to find the key and the category of the entry of a git repository, from its path
+    ///
+    fn find_entry_by_path(&self, path: &str) -> Option<(String, EntryCategory)>;
+
     ///
     /// Method to add a single entry value, represented by a string key, in a given category
     ///
@@ -111,11 +353,26 @@ pub trait ConfigureContent {
                  category: &EntryCategory)
                  -> Result<()>;
 
+    ///
+    /// Method to replace the value of an existing entry, represented by a string key, in a given
+    /// category
+    ///
+    fn update_entry(&mut self,
+                    key: &str,
+                    entry_value: &mut Entry,
+                    category: &EntryCategory)
+                    -> Result<()>;
+
     ///
     /// Method to remove a given string key, in a category
     ///
     fn remove_entry(&mut self, key: &str, category: &EntryCategory) -> Result<Value>;
 
+    ///
+    /// Method to rename a given entry (represented by a string key), in a category
+    ///
+    fn rename_entry(&mut self, key: &str, new_key: &str, category: &EntryCategory) -> Result<()>;
+
     ///
     /// Method to transfer a given entry (represented by a string key), from an old category to a
     /// new one
@@ -128,6 +385,25 @@ pub trait ConfigureContent {
 }
 
 impl ConfigureContent for ConfigurationContent {
+    ///
+    /// This method returns a Result type, that represents the decoded `config` section, or an error
+    ///
+    fn get_body(&self) -> Result<Body> {
+        match self.get(BODY_ENTRY_NAME) {
+            Some(body_value) => {
+                match toml::decode::<Body>(body_value.clone()) {
+                    Some(body) => Ok(body),
+                    None => {
+                        Err(ConfigureContentError::DecodingError(format!("Can not decode the \
+                                                                          '{}' section",
+                                                                         BODY_ENTRY_NAME)))
+                    }
+                }
+            }
+            None => Err(ConfigureContentError::UnknownKey(String::from(BODY_ENTRY_NAME))),
+        }
+    }
+
     ///
     /// This method returns a String type, that represents a complete path entry
     ///
@@ -135,10 +411,70 @@ impl ConfigureContent for ConfigurationContent {
         match category {
             &EntryCategory::Groups => format!("{}.{}", GROUPS_ENTRY_NAME, key),
             &EntryCategory::Ignored => format!("{}.{}", IGNORED_ENTRY_NAME, key),
+            &EntryCategory::Missing => format!("{}.{}", MISSING_ENTRY_NAME, key),
             &EntryCategory::Watched => format!("{}.{}", WATCHED_ENTRY_NAME, key),
         }
     }
 
+    ///
+    /// This method returns a Result type, that represents the decoded entry, or an error
+    ///
+    fn get_entry(&self, key: &str, category: &EntryCategory) -> Result<Entry> {
+        let entry_path_name = self.get_entry_path(key, category);
+        match self.get(&entry_path_name) {
+            Some(entry_value) => {
+                match toml::decode::<Entry>(entry_value.clone()) {
+                    Some(entry) => Ok(entry),
+                    None => {
+                        Err(ConfigureContentError::DecodingError(format!("Can not decode the \
+                                                                          entry '{}'",
+                                                                         entry_path_name)))
+                    }
+                }
+            }
+            None => Err(ConfigureContentError::UnknownKey(entry_path_name)),
+        }
+    }
+
+    ///
+    /// This method returns an Option type, that contains the key and the category of the entry
+    /// whose path, or one of its aliases, is the given one, or None if no entry has this path
+    /// Paths are compared once canonicalized, as they may have been written through a symbolic
+    /// link.
+    ///
+    fn find_entry_by_path(&self, path: &str) -> Option<(String, EntryCategory)> {
+        let path = canonicalize_path(Path::new(path));
+        for (entry_path_name, entry_value) in self.iter() {
+            let separator_index = match entry_path_name.find('.') {
+                Some(separator_index) => separator_index,
+                None => continue,
+            };
+            let category = match &entry_path_name[..separator_index] {
+                name if name == GROUPS_ENTRY_NAME => EntryCategory::Groups,
+                name if name == IGNORED_ENTRY_NAME => EntryCategory::Ignored,
+                name if name == MISSING_ENTRY_NAME => EntryCategory::Missing,
+                name if name == WATCHED_ENTRY_NAME => EntryCategory::Watched,
+                _ => continue,
+            };
+            let entry_table = match entry_value.as_table() {
+                Some(entry_table) => entry_table,
+                None => continue,
+            };
+            let entry_aliases = entry_table.get("aliases")
+                .and_then(|aliases| aliases.as_slice())
+                .unwrap_or(&[]);
+            let is_matching = entry_table.get("path")
+                .into_iter()
+                .chain(entry_aliases.iter())
+                .filter_map(|entry_path| entry_path.as_str())
+                .any(|entry_path| canonicalize_path(Path::new(entry_path)) == path);
+            if is_matching {
+                return Some((String::from(&entry_path_name[separator_index + 1..]), category));
+            }
+        }
+        None
+    }
+
     ///
     /// This method returns a Result type, that represents if the entry has been successfully added
     /// to the given entry, or an error
@@ -165,6 +501,32 @@ impl ConfigureContent for ConfigurationContent {
         }
     }
 
+    ///
+    /// This method returns a Result type, that represents if the entry has been successfully
+    /// replaced, or an error
+    ///
+    fn update_entry(&mut self,
+                    key: &str,
+                    entry_value: &mut Entry,
+                    category: &EntryCategory)
+                    -> Result<()> {
+        let entry_path_name = self.get_entry_path(key, category);
+        if !self.contains_key(&entry_path_name) {
+            return Err(ConfigureContentError::UnknownKey(entry_path_name));
+        }
+        entry_value.update();
+        let mut encoder = ConfigurationFile::new();
+        match entry_value.encode(&mut encoder) {
+            Ok(_) => {
+                self.insert(entry_path_name, Value::Table(encoder.toml));
+                Ok(())
+            }
+            Err(error) => {
+                Err(ConfigureContentError::EncodingError(String::from(error.description())))
+            }
+        }
+    }
+
     ///
     /// This method returns a Result type, that represents the value that been removes, or an error
     ///
@@ -183,9 +545,26 @@ impl ConfigureContent for ConfigurationContent {
         }
     }
 
+    ///
+    /// This method returns a Result type, that represents if the entry has been successfully
+    /// renamed, or an error
+    ///
+    fn rename_entry(&mut self, key: &str, new_key: &str, category: &EntryCategory) -> Result<()> {
+        let new_entry_path_name = self.get_entry_path(new_key, category);
+        if self.contains_key(&new_entry_path_name) {
+            return Err(ConfigureContentError::KeyAlreadyExists(new_entry_path_name));
+        }
+        let mut entry = self.get_entry(key, category)?;
+        entry.name = String::from(new_key);
+        self.remove_entry(key, category)?;
+        self.add_entry(new_key, &mut entry, category)
+    }
+
     ///
     /// This method returns a Result type, that represents if the entry has been successfully
     /// transfered, or an error
+    /// An entry transfered to the missing category remembers its previous category.
+    /// The entry is left in its old category if the key already exists in the new one.
     ///
     fn transfer_entry(&mut self,
                       key: &str,
@@ -196,15 +575,82 @@ impl ConfigureContent for ConfigurationContent {
             return Err(ConfigureContentError::InternalError(format!("Entry categories are \
                                                                      equals")));
         }
-        let entry_value: Value = self.remove_entry(key, old_category).unwrap();
-        match toml::decode::<Entry>(entry_value) {
-            Some(ref mut entry) => self.add_entry(key, entry, new_category),
-            None => {
-                Err(ConfigureContentError::DecodingError(format!("Can not decode the current \
-                                                                  value from '{:?}' to '{:?}'",
-                                                                 old_category,
-                                                                 new_category)))
-            }
+        let new_entry_path_name = self.get_entry_path(key, new_category);
+        if self.contains_key(&new_entry_path_name) {
+            return Err(ConfigureContentError::KeyAlreadyExists(new_entry_path_name));
         }
+        let mut entry = self.get_entry(key, old_category)?;
+        entry.category = match *new_category {
+            EntryCategory::Missing => Some(String::from(old_category.as_str())),
+            _ => None,
+        };
+        self.remove_entry(key, old_category)?;
+        self.add_entry(key, &mut entry, new_category)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_entry_key, ConfigureContent, ConfigureContentError, Entry, EntryCategory};
+    use ConfigurationContent;
+
+    fn get_keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| String::from(*key)).collect()
+    }
+
+    fn get_content(entries: &[(&str, &str, EntryCategory)]) -> ConfigurationContent {
+        let mut content = ConfigurationContent::new();
+        for &(key, path, ref category) in entries {
+            content.add_entry(key, &mut Entry::new(key, path), category).unwrap();
+        }
+        content
+    }
+
+    #[test]
+    fn transfer_entry_moves_entry_to_new_category() {
+        let mut content = get_content(&[("api", "/code/api", EntryCategory::Watched)]);
+        content.transfer_entry("api", &EntryCategory::Watched, &EntryCategory::Missing).unwrap();
+        assert!(content.get_entry("api", &EntryCategory::Watched).is_err());
+        let entry = content.get_entry("api", &EntryCategory::Missing).unwrap();
+        assert_eq!(entry.path, "/code/api");
+        assert_eq!(entry.category, Some(String::from("watched")));
+    }
+
+    #[test]
+    fn transfer_entry_keeps_entry_if_key_exists_in_new_category() {
+        let mut content = get_content(&[("api", "/code/api", EntryCategory::Watched),
+                                         ("api", "/old/api", EntryCategory::Ignored)]);
+        match content.transfer_entry("api", &EntryCategory::Watched, &EntryCategory::Ignored) {
+            Err(ConfigureContentError::KeyAlreadyExists(_)) => (),
+            _ => panic!("the transfer must fail"),
+        }
+        assert_eq!(content.get_entry("api", &EntryCategory::Watched).unwrap().path,
+                   "/code/api");
+        assert_eq!(content.get_entry("api", &EntryCategory::Ignored).unwrap().path, "/old/api");
+    }
+
+    #[test]
+    fn transfer_entry_fails_for_unknown_key() {
+        let mut content = get_content(&[]);
+        assert!(content.transfer_entry("api", &EntryCategory::Watched, &EntryCategory::Ignored)
+            .is_err());
+    }
+
+    #[test]
+    fn entry_key_is_directory_name() {
+        assert_eq!(get_entry_key("/code/api", &get_keys(&[])), "api");
+        assert_eq!(get_entry_key("/code/api/", &get_keys(&["web"])), "api");
+    }
+
+    #[test]
+    fn entry_key_is_prefixed_by_parent_directories_if_used() {
+        assert_eq!(get_entry_key("/code/oss/api", &get_keys(&["api"])), "oss/api");
+        assert_eq!(get_entry_key("/code/oss/api", &get_keys(&["api", "oss/api"])),
+                   "code/oss/api");
+    }
+
+    #[test]
+    fn entry_key_is_whole_path_if_every_suffix_is_used() {
+        assert_eq!(get_entry_key("/code/api", &get_keys(&["api", "code/api"])), "/code/api");
     }
 }
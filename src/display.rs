@@ -0,0 +1,203 @@
+use ansi_term::Style;
+use chrono::offset::utc::UTC;
+use libgyro::git::{RepoStatus, StashEntry};
+use libgyro::scan::{FoundRepository, ScanErrorKind, ScanProgress, ScanReport};
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::sync::Mutex;
+
+///
+/// A scan progress printer, that prints each root directory when its scan starts, and counts the
+/// git repositories found in it:
+/// `found`: The number of git repositories found in the current root directory
+///
+pub struct ScanPrinter {
+    found: Mutex<usize>,
+}
+
+impl ScanPrinter {
+    ///
+    /// The function to instanciate a new ScanPrinter structure
+    ///
+    pub fn new() -> Self {
+        ScanPrinter { found: Mutex::new(0) }
+    }
+}
+
+impl ScanProgress for ScanPrinter {
+    fn on_root_started(&self, root: &Path) {
+        *self.found.lock().unwrap() = 0;
+        print!("Scanning repository from {:?} to find git repositories... (this can take a while) ",
+               root);
+        io::stdout().flush().ok().expect("Could not flush stdout");
+    }
+
+    fn on_repository_found(&self, _repository: &FoundRepository) {
+        *self.found.lock().unwrap() += 1;
+    }
+
+    fn on_root_finished(&self, _root: &Path) {
+        println!("Ok! ({} found)", *self.found.lock().unwrap());
+    }
+}
+
+///
+/// Function to print warnings, like the messages of invalid patterns of the configuration file.
+///
+pub fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        println!("[WARNING] {}", warning);
+    }
+}
+
+///
+/// Function to print the summary of a scan:the number of git repositories found, of skipped
+/// directories and of errors by kind, and the duration of the scan.
+/// If `verbose` is true, each error is listed too.
+///
+pub fn print_scan_summary(report: &ScanReport, verbose: bool) {
+    println!("Found {} git repositories in {}.{:03}s ({} directories skipped, {} errors)",
+             report.repositories.len(),
+             report.elapsed.as_secs(),
+             report.elapsed.subsec_nanos() / 1_000_000,
+             report.skipped.len(),
+             report.errors.len());
+    if report.errors.is_empty() {
+        return;
+    }
+    for kind in &[ScanErrorKind::PermissionDenied,
+                  ScanErrorKind::BrokenLink,
+                  ScanErrorKind::Io,
+                  ScanErrorKind::Internal,
+                  ScanErrorKind::InvalidPattern] {
+        let count = report.count_errors(*kind);
+        if count > 0 {
+            println!("[WARNING] {} {}", count, kind);
+        }
+    }
+    if verbose {
+        for error in &report.errors {
+            println!("[WARNING] {}: {} ({})", error.path, error.kind, error.message);
+        }
+    } else {
+        println!("[WARNING] Use the --verbose flag to list them");
+    }
+}
+
+///
+/// Function to describe the time elapsed since a given time, in seconds since the Unix epoch,
+/// like `3 days ago`.
+///
+fn format_elapsed_time(time: i64) -> String {
+    let elapsed = UTC::now().timestamp() - time;
+    let units = [(365 * 24 * 3600, "year"),
+                 (30 * 24 * 3600, "month"),
+                 (7 * 24 * 3600, "week"),
+                 (24 * 3600, "day"),
+                 (3600, "hour"),
+                 (60, "minute")];
+    if elapsed < 0 {
+        return String::from("in the future");
+    }
+    for &(unit_seconds, unit_name) in &units {
+        let count = elapsed / unit_seconds;
+        if count == 1 {
+            return format!("1 {} ago", unit_name);
+        }
+        if count > 1 {
+            return format!("{} {}s ago", count, unit_name);
+        }
+    }
+    String::from("just now")
+}
+
+///
+/// Function to format a field of a repository status, with its name in bold.
+///
+fn format_status_field(name: &str, value: &str) -> String {
+    format!("|\t`{}`: {}\n", Style::new().bold().paint(name), value)
+}
+
+///
+/// Function to print the status of a git repository.
+/// A repository that cannot be opened is printed with its label (MISSING or INVALID) and the
+/// reason why.
+///
+pub fn print_repository_status(status: &RepoStatus) {
+    let mut to_display = String::new();
+    to_display += &format!("----> {}\n", Style::new().italic().paint(status.path.as_str()));
+    if !status.is_available() {
+        to_display += &format_status_field("kind?", status.kind.as_str());
+        to_display += &format_status_field("label?", status.label.as_str());
+        for error in &status.errors {
+            to_display += &format!("|\t{}\n", error);
+        }
+        println!("{}", to_display);
+        return;
+    }
+    to_display += &format_status_field("is bare?", &status.is_bare.to_string());
+    to_display += &format_status_field("kind?", status.kind.as_str());
+    to_display += &format_status_field("label?", &status.get_labels().join(" "));
+    if let Some(ref changes) = status.changes {
+        to_display += &format_status_field("changes?", &changes.to_string());
+    }
+    match status.upstream {
+        Some(ref upstream) => {
+            to_display += &format_status_field("upstream?",
+                                               &format!("{} ({} ahead, {} behind)",
+                                                        upstream.upstream,
+                                                        upstream.ahead,
+                                                        upstream.behind))
+        }
+        None => to_display += &format_status_field("upstream?", "none"),
+    }
+    match status.operation {
+        Some(ref operation) => {
+            to_display += &format_status_field("in progress?", &operation.to_string())
+        }
+        None => to_display += &format_status_field("in progress?", "none"),
+    }
+    for remote in &status.remotes {
+        to_display += &format!("|\tExisting remote: {}\n", remote);
+    }
+    to_display += &format_status_field("stashes?", &status.stashes.to_string());
+    match status.head_commit {
+        Some(ref commit) => {
+            to_display += &format_status_field("last commit?",
+                                               &format!("{} {} ({}, {})",
+                                                        commit.short_id,
+                                                        commit.summary,
+                                                        commit.author,
+                                                        format_elapsed_time(commit.time)))
+        }
+        None => to_display += &format_status_field("last commit?", "none"),
+    }
+    if let Some(ref head) = status.head {
+        to_display += &format!("|\tHead: {}\n", head);
+    }
+    for error in &status.errors {
+        to_display += &format!("|\t[ERROR] {}\n", error);
+    }
+    println!("{}", to_display);
+}
+
+///
+/// Function to print the stashes of a git repository, with their message, branch and age.
+///
+pub fn print_stashes(path: &str, stashes: &[StashEntry]) {
+    let mut to_display = String::new();
+    to_display += &format!("----> {}\n", Style::new().italic().paint(path));
+    for stash in stashes {
+        to_display += &format!("|\t{}: {} ({}, {}, {})\n",
+                               Style::new().bold().paint(format!("stash@{{{}}}", stash.index)),
+                               stash.message,
+                               match stash.branch {
+                                   Some(ref branch) => format!("on {}", branch),
+                                   None => String::from("on a detached HEAD"),
+                               },
+                               stash.id,
+                               format_elapsed_time(stash.time));
+    }
+    println!("{}", to_display);
+}
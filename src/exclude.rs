@@ -0,0 +1,226 @@
+use glob::{MatchOptions, Pattern};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+///
+/// Options used to match a path against an exclusion pattern: wildcards never match a path
+/// separator, as in gitignore files
+///
+static MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+///
+/// An exclusion pattern, as written in the configuration file or in a .gyroignore file:
+/// `pattern`: The glob pattern to match
+/// `negated`: True if the pattern started with `!`, to include again a previously excluded path
+/// `anchored`: True if the pattern contains a `/`, to match the path relative to the base
+/// directory instead of the directory name only
+///
+#[derive(Clone)]
+struct ExcludePattern {
+    pattern: Pattern,
+    negated: bool,
+    anchored: bool,
+}
+
+impl ExcludePattern {
+    ///
+    /// This function returns a Result type, that contains the pattern parsed from a line of a
+    /// gitignore-style file (None if the line is empty or a comment), or a message if the line is
+    /// not a valid glob
+    ///
+    fn parse(line: &str) -> Result<Option<Self>, String> {
+        let mut line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let negated = line.starts_with('!');
+        if negated {
+            line = &line[1..];
+        }
+        // Only directories are matched, so the trailing separator does not change anything
+        while line.ends_with('/') {
+            line = &line[..line.len() - 1];
+        }
+        let anchored = line.contains('/');
+        while line.starts_with('/') {
+            line = &line[1..];
+        }
+        match Pattern::new(line) {
+            Ok(pattern) => {
+                Ok(Some(ExcludePattern {
+                    pattern: pattern,
+                    negated: negated,
+                    anchored: anchored,
+                }))
+            }
+            Err(error) => Err(format!("Invalid exclusion pattern '{}': {}", line, error)),
+        }
+    }
+
+    ///
+    /// This method returns a boolean - true if the given path, relative to the base directory,
+    /// matches the pattern, else false
+    ///
+    fn matches(&self, relative_path: &Path) -> bool {
+        if self.anchored {
+            return self.pattern.matches_path_with(relative_path, &MATCH_OPTIONS);
+        }
+        match relative_path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => self.pattern.matches_with(name, &MATCH_OPTIONS),
+            None => false,
+        }
+    }
+}
+
+///
+/// A list of exclusion patterns, relative to a base directory:
+/// `patterns`: The valid patterns
+/// `errors`: The messages of the invalid patterns, that are ignored
+///
+/// As in gitignore files, the last matching pattern decides if a path is excluded or not.
+///
+#[derive(Clone)]
+pub struct ExcludeRules {
+    patterns: Vec<ExcludePattern>,
+    errors: Vec<String>,
+}
+
+impl ExcludeRules {
+    ///
+    /// The function to instanciate a new ExcludeRules structure, from a list of patterns
+    ///
+    pub fn new(patterns: &[String]) -> Self {
+        let mut rules = ExcludeRules {
+            patterns: Vec::new(),
+            errors: Vec::new(),
+        };
+        for pattern in patterns {
+            match ExcludePattern::parse(pattern) {
+                Ok(Some(pattern)) => rules.patterns.push(pattern),
+                Ok(None) => (),
+                Err(error) => rules.errors.push(error),
+            }
+        }
+        rules
+    }
+
+    ///
+    /// This method returns the messages of the invalid patterns, that are ignored
+    ///
+    pub fn get_errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    ///
+    /// This function returns an Option type, that contains the patterns of a .gyroignore file, or
+    /// None if the file cannot be read
+    ///
+    pub fn from_file(path: &Path) -> Option<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return None,
+        };
+        let patterns = BufReader::new(file)
+            .lines()
+            .filter_map(|line| line.ok())
+            .collect::<Vec<String>>();
+        Some(ExcludeRules::new(&patterns))
+    }
+
+    ///
+    /// This method returns an Option type: Some(true) if the given path, relative to the base
+    /// directory, is excluded, Some(false) if it is explicitly included, or None if no pattern
+    /// matches it
+    ///
+    pub fn is_excluded(&self, relative_path: &Path) -> Option<bool> {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(relative_path))
+            .map(|pattern| !pattern.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExcludeRules;
+    use std::path::Path;
+
+    fn get_rules(patterns: &[&str]) -> ExcludeRules {
+        ExcludeRules::new(&patterns.iter()
+            .map(|pattern| String::from(*pattern))
+            .collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn unanchored_pattern_matches_directory_name_at_any_depth() {
+        let rules = get_rules(&["node_modules"]);
+        assert_eq!(rules.is_excluded(Path::new("node_modules")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("code/app/node_modules")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("code/node_modules_old")), None);
+    }
+
+    #[test]
+    fn anchored_pattern_matches_path_from_base_directory() {
+        let rules = get_rules(&["/target", "code/build"]);
+        assert_eq!(rules.is_excluded(Path::new("target")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("code/target")), None);
+        assert_eq!(rules.is_excluded(Path::new("code/build")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("other/code/build")), None);
+    }
+
+    #[test]
+    fn wildcard_does_not_match_path_separator() {
+        let rules = get_rules(&["code/*"]);
+        assert_eq!(rules.is_excluded(Path::new("code/app")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("code/app/src")), None);
+    }
+
+    #[test]
+    fn double_star_matches_any_number_of_directories() {
+        let rules = get_rules(&[".cargo/**/registry"]);
+        assert_eq!(rules.is_excluded(Path::new(".cargo/registry")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new(".cargo/a/b/registry")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("registry")), None);
+    }
+
+    #[test]
+    fn invalid_pattern_is_returned_as_an_error() {
+        let rules = get_rules(&["# comment", "", "target", "build[", "!/logs"]);
+        assert_eq!(rules.get_errors().len(), 1);
+        assert!(rules.get_errors()[0].contains("build["));
+        assert_eq!(rules.is_excluded(Path::new("target")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("build[")), None);
+    }
+
+    #[test]
+    fn trailing_separator_is_ignored() {
+        let rules = get_rules(&["build/", "/vendor//"]);
+        assert_eq!(rules.is_excluded(Path::new("app/build")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("vendor")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("app/vendor")), None);
+    }
+
+    #[test]
+    fn last_matching_pattern_decides() {
+        let rules = get_rules(&["vendor*", "!vendor-keep", "vendor-keep/old"]);
+        assert_eq!(rules.is_excluded(Path::new("vendor-old")), Some(true));
+        assert_eq!(rules.is_excluded(Path::new("app/vendor-keep")), Some(false));
+        assert_eq!(rules.is_excluded(Path::new("vendor-keep/old")), Some(true));
+        let rules = get_rules(&["!vendor-keep", "vendor*"]);
+        assert_eq!(rules.is_excluded(Path::new("vendor-keep")), Some(true));
+    }
+
+    #[test]
+    fn empty_lines_comments_and_invalid_patterns_are_skipped() {
+        let rules = get_rules(&["", "   ", "# target", "[", "target"]);
+        assert_eq!(rules.is_excluded(Path::new("#target")), None);
+        assert_eq!(rules.is_excluded(Path::new("[")), None);
+        assert_eq!(rules.is_excluded(Path::new("target")), Some(true));
+    }
+}
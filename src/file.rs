@@ -4,7 +4,7 @@ use std::path::Path;
 use toml::{encode_str, Parser, Value};
 
 use {ConfigurationContent, ConfigurationFile, BODY_ENTRY_NAME, WATCHED_ENTRY_NAME,
-     IGNORED_ENTRY_NAME, GROUPS_ENTRY_NAME};
+     IGNORED_ENTRY_NAME, MISSING_ENTRY_NAME, GROUPS_ENTRY_NAME};
 
 pub trait ConfigurationFileExtension {
     ///
@@ -27,17 +27,30 @@ impl<'a> ConfigurationFileExtension for ConfigurationFile {
         let toml_content = format!(r#"
             [{}]
             store = "{}"
+            roots = []
+            exclude = []
+            nested = false
+            hidden = false
+            hidden_allow = []
+            hidden_deny = []
+            one_file_system = false
+            skip_mount_types = []
+            skip_mounts = []
+            include = []
 
             [{}]
             
             [{}]
 
+            [{}]
+
             [{}]
             "#,
                                    BODY_ENTRY_NAME,
                                    WATCHED_ENTRY_NAME,
                                    WATCHED_ENTRY_NAME,
                                    IGNORED_ENTRY_NAME,
+                                   MISSING_ENTRY_NAME,
                                    GROUPS_ENTRY_NAME);
         encoder.toml = match Parser::new(&toml_content).parse() {
             Some(encoder) => encoder,
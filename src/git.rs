@@ -1,7 +1,234 @@
-use ansi_term::Style;
-use git2::Repository;
+use {GIT_DIR_NAME, GIT_HEAD_FILE_NAME, GITDIR_FILE_PREFIX};
+use cache::{CachedRootCommit, ScanCache};
+use operation::{get_operation, get_state_from_git_dir, Operation};
+use git2::{BranchType, Error, ErrorCode, Index, Oid, Repository, StatusOptions, StatusShow};
+use git2::{STATUS_CONFLICTED, STATUS_INDEX_DELETED, STATUS_INDEX_MODIFIED, STATUS_INDEX_NEW,
+           STATUS_INDEX_RENAMED, STATUS_INDEX_TYPECHANGE, STATUS_WT_DELETED, STATUS_WT_MODIFIED,
+           STATUS_WT_NEW, STATUS_WT_RENAMED, STATUS_WT_TYPECHANGE};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-pub struct Repo(pub Repository);
+pub struct Repo(pub Repository, pub RepositoryKind);
+
+///
+/// The kind of a git repository:
+/// `Bare` is corresponding to a git repository without working directory
+/// `Normal` is corresponding to a working directory that contains its git directory
+/// `Submodule` is corresponding to a submodule checkout, which git directory is in its parent
+/// repository
+/// `Worktree` is corresponding to a linked working tree of another git repository
+///
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum RepositoryKind {
+    Bare,
+    Normal,
+    Submodule,
+    Worktree,
+}
+
+impl RepositoryKind {
+    ///
+    /// This method returns the name of the kind, as stored in the configuration file
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            RepositoryKind::Bare => "bare",
+            RepositoryKind::Normal => "normal",
+            RepositoryKind::Submodule => "submodule",
+            RepositoryKind::Worktree => "worktree",
+        }
+    }
+}
+
+impl fmt::Display for RepositoryKind {
+    ///
+    /// Function that format the kind to display
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl FromStr for RepositoryKind {
+    type Err = String;
+
+    ///
+    /// Function that returns the kind corresponding to its name
+    ///
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "bare" => Ok(RepositoryKind::Bare),
+            "normal" => Ok(RepositoryKind::Normal),
+            "submodule" => Ok(RepositoryKind::Submodule),
+            "worktree" => Ok(RepositoryKind::Worktree),
+            _ => Err(format!("Unknown kind of git repository '{}'", kind)),
+        }
+    }
+}
+
+///
+/// The number of changed files in a git repository, by kind of change:
+/// `staged`: The files whose changes are in the index, but not committed yet
+/// `unstaged`: The tracked files whose changes are not in the index
+/// `untracked`: The new files that are not in the index, nor ignored
+/// `conflicted`: The files with unresolved conflicts
+///
+/// A file with staged and unstaged changes is counted in both.
+///
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ChangeCounts {
+    pub staged: usize,
+    pub unstaged: usize,
+    pub untracked: usize,
+    pub conflicted: usize,
+}
+
+impl ChangeCounts {
+    ///
+    /// This method returns a boolean - true if there is not any change, else false
+    ///
+    pub fn is_clean(&self) -> bool {
+        self.staged == 0 && self.unstaged == 0 && self.untracked == 0 && self.conflicted == 0
+    }
+}
+
+impl fmt::Display for ChangeCounts {
+    ///
+    /// Function that format the counts to display
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{} staged, {} unstaged, {} untracked, {} conflicted",
+               self.staged,
+               self.unstaged,
+               self.untracked,
+               self.conflicted)
+    }
+}
+
+///
+/// The synchronisation of the current branch of a git repository with its upstream:
+/// `upstream`: The name of the upstream branch, like `origin/master`
+/// `ahead`: The number of commits of the current branch that are not on its upstream
+/// `behind`: The number of commits of the upstream that are not on the current branch
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpstreamCounts {
+    pub upstream: String,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+///
+/// A commit of a git repository, as shown in its status:
+/// `id`: The id of the commit
+/// `short_id`: The shortest unambiguous abbreviation of the id
+/// `summary`: The first line of the commit message
+/// `author`: The name of the author
+/// `time`: The time of the commit, in seconds since the Unix epoch
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommitDetails {
+    pub id: String,
+    pub short_id: String,
+    pub summary: String,
+    pub author: String,
+    pub time: i64,
+}
+
+///
+/// The HEAD of a git repository:
+/// `Branch` is corresponding to a local branch, given by its name
+/// `Detached` is corresponding to a commit that is not on a branch, given by its short id
+/// `Unborn` is corresponding to a branch without any commit yet, like after `git init`, given by
+/// its name
+/// `Broken` is corresponding to a HEAD that cannot be read, like a corrupted reference, given by
+/// the reason why
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HeadState {
+    Branch(String),
+    Detached(String),
+    Unborn(String),
+    Broken(String),
+}
+
+impl fmt::Display for HeadState {
+    ///
+    /// Function that format the HEAD to display
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HeadState::Branch(ref branch) => write!(f, "{}", branch),
+            HeadState::Detached(ref commit) => write!(f, "detached at {}", commit),
+            HeadState::Unborn(ref branch) => write!(f, "{} (no commit yet)", branch),
+            HeadState::Broken(ref reason) => write!(f, "broken ({})", reason),
+        }
+    }
+}
+
+///
+/// A stash of a git repository:
+/// `index`: The position of the stash, as in `stash@{0}` for the last one
+/// `id`: The short id of the stash commit
+/// `branch`: The branch the stash has been created on - None for a detached HEAD
+/// `message`: The message of the stash, without its branch
+/// `time`: The time of the stash, in seconds since the Unix epoch
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StashEntry {
+    pub index: usize,
+    pub id: String,
+    pub branch: Option<String>,
+    pub message: String,
+    pub time: i64,
+}
+
+///
+/// Name of the file that contains the path of the main repository, in the git directory of a
+/// linked working tree.
+///
+pub static WORKTREE_COMMONDIR_FILE_NAME: &'static str = "commondir";
+
+///
+/// Name of the index file, in the git directory of a linked working tree.
+///
+static WORKTREE_INDEX_FILE_NAME: &'static str = "index";
+
+///
+/// Prefix of a HEAD file that points to a reference.
+///
+static HEAD_REF_PREFIX: &'static str = "ref:";
+
+///
+/// Prefix of the references of local branches.
+///
+static BRANCH_REF_PREFIX: &'static str = "refs/heads/";
+
+///
+/// Name of the remote preferred to identify a repository.
+///
+static ORIGIN_REMOTE_NAME: &'static str = "origin";
+
+///
+/// Reference of the stashes - each stash is an entry of its reflog.
+///
+static STASH_REF_NAME: &'static str = "refs/stash";
+
+///
+/// Prefixes of the messages of stashes, before the name of their branch.
+///
+static STASH_MESSAGE_PREFIXES: [&'static str; 2] = ["WIP on ", "On "];
+
+///
+/// Branch written in the message of a stash created on a detached HEAD.
+///
+static STASH_NO_BRANCH: &'static str = "(no branch)";
 
 ///
 /// Label for a repository that indicates that the repository is clean.
@@ -14,85 +241,886 @@ static CLEAN_LABEL: &'static str = "CLEAN";
 static DIRTY_LABEL: &'static str = "DIRTY";
 
 ///
-/// Function to get some informations about git repositories indicated by their path.
+/// Label for a repository whose current branch has commits that are not on its upstream.
+///
+static UNPUSHED_LABEL: &'static str = "UNPUSHED";
+
+///
+/// Label for a repository whose current branch is missing commits of its upstream.
+///
+static BEHIND_LABEL: &'static str = "BEHIND";
+
+///
+/// Label for a repository that is not on the disk anymore.
+///
+static MISSING_LABEL: &'static str = "MISSING";
+
+///
+/// Label for a path that is not a git repository, or that cannot be opened as one.
+///
+static INVALID_LABEL: &'static str = "INVALID";
+
+///
+/// The label of a git repository:
+/// `Clean` is corresponding to a repository without any change
+/// `Dirty` is corresponding to a repository with changes, or whose changes cannot be read
+/// `Invalid` is corresponding to a path that is not a git repository, or that cannot be opened
+/// `Missing` is corresponding to a repository that is not on the disk anymore
 ///
-pub fn get_statuses_from(vector_of_repositories: &Vec<String>,
-                         get_only_clean: bool,
-                         get_only_dirty: bool) {
-    let valid_path_repositories = vector_of_repositories.iter()
-        .filter(|path| Repository::init(path).is_ok())
-        .collect::<Vec<&String>>();
-    let git_objects = valid_path_repositories.iter()
-        .map(|path| Repo(Repository::init(path).unwrap()));
-    let filtered_git_objects = git_objects
-        .filter(|repo| repo.is_clean() == get_only_clean || !repo.is_clean() == get_only_dirty)
-        .collect::<Vec<_>>();
-    for object in filtered_git_objects {
-        object.get_status();
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RepoLabel {
+    Clean,
+    Dirty,
+    Invalid,
+    Missing,
+}
+
+impl RepoLabel {
+    ///
+    /// This method returns the name of the label, as printed in statuses
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            RepoLabel::Clean => CLEAN_LABEL,
+            RepoLabel::Dirty => DIRTY_LABEL,
+            RepoLabel::Invalid => INVALID_LABEL,
+            RepoLabel::Missing => MISSING_LABEL,
+        }
     }
 }
 
-impl Repo {
+impl fmt::Display for RepoLabel {
+    ///
+    /// Function that format the label to display
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+///
+/// The status of a git repository, as computed by `Repo::get_status`:
+/// `path`: The local path of the repository, as it has been given - its saved path, whatever the
+/// kind of the repository, or if it is missing or invalid
+/// `kind`: The kind of the repository
+/// `is_bare`: If the repository does not have any working directory
+/// `head`: The current branch, or the commit of a detached HEAD
+/// `head_commit`: The last commit of the current branch
+/// `label`: If the repository is clean, dirty, missing or invalid
+/// `operation`: The operation in progress in the repository, like a rebase
+/// `remotes`: The names of the remotes
+/// `changes`: The number of changed files, by kind of change
+/// `upstream`: The synchronisation of the current branch with its upstream
+/// `stashes`: The number of stashes - always 0 for a linked working tree, whose stashes are
+/// the ones of its main repository
+/// `errors`: The errors encountered while reading the repository
+///
+#[derive(Clone, Debug)]
+pub struct RepoStatus {
+    pub path: String,
+    pub kind: RepositoryKind,
+    pub is_bare: bool,
+    pub head: Option<HeadState>,
+    pub head_commit: Option<CommitDetails>,
+    pub label: RepoLabel,
+    pub operation: Option<Operation>,
+    pub remotes: Vec<String>,
+    pub changes: Option<ChangeCounts>,
+    pub upstream: Option<UpstreamCounts>,
+    pub stashes: usize,
+    pub errors: Vec<String>,
+}
+
+impl RepoStatus {
     ///
-    /// Function to know if a given Repo object is clean or not.
-    /// A clean repository is a repository that does not have any difference with the last git index.
+    /// The function to instanciate the status of a repository that cannot be opened: it is
+    /// MISSING if its path does not exist anymore, else INVALID
+    ///
+    fn unavailable(path: &str, kind: RepositoryKind, error: &Error) -> Self {
+        let (label, reason) = if Path::new(path).exists() {
+            (RepoLabel::Invalid, String::from(error.message()))
+        } else {
+            (RepoLabel::Missing, String::from("Not on the disk anymore"))
+        };
+        RepoStatus {
+            path: String::from(path),
+            kind: kind,
+            is_bare: false,
+            head: None,
+            head_commit: None,
+            label: label,
+            operation: None,
+            remotes: Vec::new(),
+            changes: None,
+            upstream: None,
+            stashes: 0,
+            errors: vec![reason],
+        }
+    }
+
+    ///
+    /// This method returns a boolean - true if the repository does not have any change, else false
     ///
     pub fn is_clean(&self) -> bool {
-        let &Repo(ref repository) = self;
-        let current_git_index = repository.index();
-        match current_git_index {
-            Ok(_) => {
-                let previous_index =
-                    repository.diff_index_to_workdir(Some(&(current_git_index.unwrap())), None)
-                        .unwrap();
-                let deltas = previous_index.deltas();
-                deltas.count() == 0
+        self.label == RepoLabel::Clean
+    }
+
+    ///
+    /// This method returns a boolean - true if the repository can be opened, else false
+    ///
+    pub fn is_available(&self) -> bool {
+        self.label != RepoLabel::Invalid && self.label != RepoLabel::Missing
+    }
+
+    ///
+    /// This method returns the labels of the repository: its label, followed by UNPUSHED if the
+    /// current branch has commits to push, by BEHIND if it has commits to pull, and by the label
+    /// of the operation in progress, like REBASING
+    ///
+    pub fn get_labels(&self) -> Vec<&'static str> {
+        let mut labels = vec![self.label.as_str()];
+        if let Some(ref operation) = self.operation {
+            labels.push(operation.as_label());
+        }
+        if let Some(ref upstream) = self.upstream {
+            if upstream.ahead > 0 {
+                labels.push(UNPUSHED_LABEL);
+            }
+            if upstream.behind > 0 {
+                labels.push(BEHIND_LABEL);
+            }
+        }
+        labels
+    }
+}
+
+///
+/// Function to read the content of a small file, like the ones of a git directory.
+///
+fn read_file(path: &Path) -> Option<String> {
+    let mut content = String::new();
+    match File::open(path) {
+        Ok(mut file) => {
+            match file.read_to_string(&mut content) {
+                Ok(_) => Some(content),
+                Err(_) => None,
+            }
+        }
+        Err(_) => None,
+    }
+}
+
+///
+/// Function to get the name of a branch from its reference, like `master` for `refs/heads/master`.
+///
+fn get_branch_name(reference: &str) -> String {
+    if reference.starts_with(BRANCH_REF_PREFIX) {
+        return String::from(&reference[BRANCH_REF_PREFIX.len()..]);
+    }
+    String::from(reference)
+}
+
+///
+/// Function to split the message of a stash, like `WIP on master: 1a2b3c4 Fix the parser`, into
+/// its branch and its own message.
+///
+fn parse_stash_message(stash_message: &str) -> (Option<String>, String) {
+    let without_prefix = STASH_MESSAGE_PREFIXES.iter()
+        .find(|prefix| stash_message.starts_with(*prefix))
+        .map(|prefix| &stash_message[prefix.len()..]);
+    let separator = without_prefix.and_then(|rest| rest.find(": ").map(|index| (rest, index)));
+    let (branch, message) = match separator {
+        Some((rest, index)) => (&rest[..index], &rest[index + 2..]),
+        None => return (None, String::from(stash_message)),
+    };
+    if branch == STASH_NO_BRANCH {
+        return (None, String::from(message));
+    }
+    (Some(String::from(branch)), String::from(message))
+}
+
+///
+/// Function to get the git directory written in a GIT_DIR_NAME file, as used by linked working
+/// trees and submodules.
+///
+/// This function returns an Option type, that contains the path of the git directory, or None if
+/// the file is not a git file.
+///
+pub fn get_gitdir_from_file(gitdir_file: &Path) -> Option<PathBuf> {
+    let content = match read_file(gitdir_file) {
+        Some(content) => content,
+        None => return None,
+    };
+    if !content.starts_with(GITDIR_FILE_PREFIX) {
+        return None;
+    }
+    let git_dir = PathBuf::from(content[GITDIR_FILE_PREFIX.len()..].trim());
+    match gitdir_file.parent() {
+        Some(parent) if git_dir.is_relative() => Some(parent.join(git_dir)),
+        _ => Some(git_dir),
+    }
+}
+
+///
+/// Function to open a linked working tree.
+///
+/// The git directory of a linked working tree only contains its own HEAD and index, so the main
+/// repository (written in the `commondir` file) is opened, with the working tree as working
+/// directory and the index of the working tree.
+///
+fn open_worktree(path: &Path) -> Result<Repository, Error> {
+    let git_dir = match get_gitdir_from_file(&path.join(GIT_DIR_NAME)) {
+        Some(git_dir) => git_dir,
+        None => return Err(Error::from_str("Not a linked working tree")),
+    };
+    let common_dir = match read_file(&git_dir.join(WORKTREE_COMMONDIR_FILE_NAME)) {
+        Some(common_dir) => git_dir.join(common_dir.trim()),
+        None => return Err(Error::from_str("No main repository for the linked working tree")),
+    };
+    let repository = Repository::open(common_dir)?;
+    repository.set_workdir(path, false)?;
+    let mut index = Index::open(&git_dir.join(WORKTREE_INDEX_FILE_NAME))?;
+    repository.set_index(&mut index);
+    Ok(repository)
+}
+
+///
+/// Function to open an existing git repository, depending on its kind.
+/// A path that is not a git repository is never initialised as a new one, and its parent
+/// directories are not searched.
+///
+fn open_repository(path: &str, kind: RepositoryKind) -> Result<Repository, Error> {
+    match kind {
+        RepositoryKind::Worktree => open_worktree(Path::new(path)),
+        _ => Repository::open(path),
+    }
+}
+
+///
+/// Function to get the url of the `origin` remote of a git repository, or of its first remote.
+///
+fn get_remote_url(repository: &Repository) -> Option<String> {
+    repository.find_remote(ORIGIN_REMOTE_NAME)
+        .ok()
+        .or_else(|| {
+            repository.remotes()
+                .ok()
+                .and_then(|remotes| remotes.get(0).map(String::from))
+                .and_then(|remote_name| repository.find_remote(&remote_name).ok())
+        })
+        .and_then(|remote| remote.url().map(String::from))
+}
+
+///
+/// Function to get the url of the `origin` remote of a git repository indicated by its path and
+/// kind, or of its first remote.
+///
+/// This function returns None if the repository cannot be opened, or does not have any remote.
+///
+pub fn get_repository_remote_url(path: &str, kind: RepositoryKind) -> Option<String> {
+    open_repository(path, kind).ok().and_then(|repository| get_remote_url(&repository))
+}
+
+///
+/// Function to get the id of the root commit of HEAD, following first parents.
+///
+/// The root commit is read from the scan cache if HEAD has not changed since it has been cached -
+/// else it is found by walking the history, and cached.
+///
+fn get_root_commit(repository: &Repository, path: &str, cache: &mut ScanCache) -> Option<String> {
+    let head = match repository.head().ok().and_then(|head| head.target()) {
+        Some(head) => head.to_string(),
+        None => return None,
+    };
+    if let Some(cached_root_commit) = cache.root_commits.get(path) {
+        if cached_root_commit.head == head {
+            return Some(cached_root_commit.root_commit.clone());
+        }
+    }
+    let root_commit = {
+        let mut revwalk = match repository.revwalk() {
+            Ok(revwalk) => revwalk,
+            Err(_) => return None,
+        };
+        if revwalk.push_head().is_err() {
+            return None;
+        }
+        revwalk.simplify_first_parent();
+        match revwalk.filter_map(|oid| oid.ok()).last() {
+            Some(oid) => oid.to_string(),
+            None => return None,
+        }
+    };
+    cache.root_commits.insert(String::from(path),
+                              CachedRootCommit {
+                                  head: head,
+                                  root_commit: root_commit.clone(),
+                              });
+    Some(root_commit)
+}
+
+///
+/// Function to get the identity of a git repository, that does not change if the repository is
+/// moved.
+///
+/// This function returns an Option type, that contains the id of the root commit of HEAD (following
+/// first parents), followed by the url of the `origin` remote (or of the first remote) if any - or
+/// None if the repository cannot be opened or does not contain any commit.
+/// The root commit is cached in the scan cache, so the history of a repository is only walked
+/// again when its HEAD has changed.
+///
+pub fn get_repository_identity(path: &str,
+                               kind: RepositoryKind,
+                               cache: &mut ScanCache)
+                               -> Option<String> {
+    let repository = match open_repository(path, kind) {
+        Ok(repository) => repository,
+        Err(_) => return None,
+    };
+    let root_commit = match get_root_commit(&repository, path, cache) {
+        Some(root_commit) => root_commit,
+        None => return None,
+    };
+    match get_remote_url(&repository) {
+        Some(remote_url) => Some(format!("{} {}", root_commit, remote_url)),
+        None => Some(root_commit),
+    }
+}
+
+///
+/// Function to know if two identities of git repositories, from `get_repository_identity`, are
+/// the ones of the same repository: they have the same root commit.
+///
+/// Remote urls are not compared, so a repository whose url has changed (from https to ssh, for
+/// example) is still the same one - they can only be used to choose between several repositories
+/// with the same root commit.
+///
+pub fn is_same_identity(identity: &str, other_identity: &str) -> bool {
+    identity.split(' ').next() == other_identity.split(' ').next()
+}
+
+///
+/// A builder of the filter of the statuses of git repositories:
+/// `clean`: Only keep the clean repositories
+/// `dirty`: Only keep the dirty repositories, and the ones that cannot be opened
+/// `unpushed`: Only keep the repositories whose current branch is ahead of its upstream
+/// `behind`: Only keep the repositories whose current branch is behind its upstream
+/// `in_progress`: Only keep the repositories with an operation in progress, like a rebase
+///
+/// Both `clean` and `dirty` keep every repository, and both `unpushed` and `behind` keep the
+/// repositories whose current branch is ahead of its upstream, or behind it.
+///
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatusFilter {
+    clean: bool,
+    dirty: bool,
+    unpushed: bool,
+    behind: bool,
+    in_progress: bool,
+}
+
+impl StatusFilter {
+    ///
+    /// The function to instanciate a new StatusFilter structure, that keeps every status
+    ///
+    pub fn new() -> Self {
+        StatusFilter::default()
+    }
+
+    ///
+    /// A method to only keep the clean repositories
+    ///
+    pub fn clean(mut self, clean: bool) -> Self {
+        self.clean = clean;
+        self
+    }
+
+    ///
+    /// A method to only keep the dirty repositories, and the ones that cannot be opened
+    ///
+    pub fn dirty(mut self, dirty: bool) -> Self {
+        self.dirty = dirty;
+        self
+    }
+
+    ///
+    /// A method to only keep the repositories whose current branch has commits to push
+    ///
+    pub fn unpushed(mut self, unpushed: bool) -> Self {
+        self.unpushed = unpushed;
+        self
+    }
+
+    ///
+    /// A method to only keep the repositories whose current branch has commits to pull
+    ///
+    pub fn behind(mut self, behind: bool) -> Self {
+        self.behind = behind;
+        self
+    }
+
+    ///
+    /// A method to only keep the repositories with an operation in progress
+    ///
+    pub fn in_progress(mut self, in_progress: bool) -> Self {
+        self.in_progress = in_progress;
+        self
+    }
+
+    ///
+    /// This method returns a boolean - true if the status is kept by the filter, else false
+    ///
+    pub fn matches(&self, status: &RepoStatus) -> bool {
+        let filter_upstream = self.unpushed || self.behind;
+        if !status.is_available() {
+            return !(self.clean && !self.dirty) && !filter_upstream && !self.in_progress;
+        }
+        if self.clean != self.dirty && status.is_clean() != self.clean {
+            return false;
+        }
+        if self.in_progress && status.operation.is_none() {
+            return false;
+        }
+        if !filter_upstream {
+            return true;
+        }
+        match status.upstream {
+            Some(ref counts) => {
+                (self.unpushed && counts.ahead > 0) || (self.behind && counts.behind > 0)
             }
-            Err(_) => false,
+            None => false,
         }
     }
+}
+
+///
+/// Function to get the statuses of git repositories indicated by their path and kind, that are
+/// kept by the given filter.
+/// Repositories are opened as they are, and are never created: the ones that are not on the disk
+/// anymore are reported as MISSING, and the ones that cannot be opened as INVALID, along with the
+/// dirty ones.
+///
+pub fn get_statuses_from(vector_of_repositories: &Vec<(String, RepositoryKind)>,
+                         filter: &StatusFilter)
+                         -> Vec<RepoStatus> {
+    vector_of_repositories.iter()
+        .map(|&(ref path, kind)| match open_repository(path, kind) {
+            Ok(repository) => Repo(repository, kind).get_status(path),
+            Err(error) => RepoStatus::unavailable(path, kind, &error),
+        })
+        .filter(|status| filter.matches(status))
+        .collect()
+}
+
+///
+/// Function to get the stashes of git repositories indicated by their path and kind.
+/// If `created_before` is given, only the stashes created before this time (in seconds since the
+/// Unix epoch) are kept.
+///
+/// This function returns the path of each repository with stashes, and its stashes - the
+/// repositories that cannot be opened are not listed.
+/// The stashes of a linked working tree are the ones of its main repository, so they are only
+/// listed once, for the main repository if it is given, else for the first working tree.
+///
+pub fn get_stashes_from(vector_of_repositories: &Vec<(String, RepositoryKind)>,
+                        created_before: Option<i64>)
+                        -> Vec<(String, Vec<StashEntry>)> {
+    let mut listed_git_dirs = HashSet::new();
+    vector_of_repositories.iter()
+        .filter(|&&(_, kind)| kind != RepositoryKind::Worktree)
+        .chain(vector_of_repositories.iter()
+            .filter(|&&(_, kind)| kind == RepositoryKind::Worktree))
+        .filter_map(|&(ref path, kind)| {
+            open_repository(path, kind).ok().map(|repository| (path, Repo(repository, kind)))
+        })
+        .filter(|&(_, Repo(ref repository, _))| {
+            let git_dir = repository.path();
+            listed_git_dirs.insert(fs::canonicalize(git_dir).unwrap_or_else(|_| git_dir.into()))
+        })
+        .map(|(path, repo)| {
+            let stashes = repo.get_stashes()
+                .into_iter()
+                .filter(|stash| created_before.map_or(true, |time| stash.time < time))
+                .collect::<Vec<StashEntry>>();
+            (path.clone(), stashes)
+        })
+        .filter(|&(_, ref stashes)| !stashes.is_empty())
+        .collect()
+}
 
+impl Repo {
     ///
-    /// Function to return a label, corresponding to the 'clean status' of the repository.
-    /// A label indicates if a repository is clean or not.
+    /// Function to count the changed files of the repository, from its full status: the index
+    /// against HEAD, the working directory against the index, untracked and conflicted files.
+    /// A bare repository, that does not have any working directory, does not have any change.
     ///
-    pub fn get_label(&self) -> String {
-        if self.is_clean() {
-            return String::from("CLEAN");
+    pub fn get_change_counts(&self) -> Result<ChangeCounts, Error> {
+        let &Repo(ref repository, ref kind) = self;
+        let mut counts = ChangeCounts::default();
+        if repository.is_bare() {
+            return Ok(counts);
         }
-        String::from("DIRTY")
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).include_ignored(false);
+        // The HEAD of a linked working tree is not the one of its main repository, so its staged
+        // changes are counted apart
+        let is_worktree = *kind == RepositoryKind::Worktree;
+        if is_worktree {
+            options.show(StatusShow::Workdir);
+            counts.staged = self.count_worktree_staged_changes()?;
+        }
+        for entry in repository.statuses(Some(&mut options))?.iter() {
+            let status = entry.status();
+            if status.contains(STATUS_CONFLICTED) {
+                counts.conflicted += 1;
+                continue;
+            }
+            if !is_worktree &&
+               status.intersects(STATUS_INDEX_NEW | STATUS_INDEX_MODIFIED | STATUS_INDEX_DELETED |
+                                 STATUS_INDEX_RENAMED |
+                                 STATUS_INDEX_TYPECHANGE) {
+                counts.staged += 1;
+            }
+            if status.intersects(STATUS_WT_MODIFIED | STATUS_WT_DELETED | STATUS_WT_RENAMED |
+                                 STATUS_WT_TYPECHANGE) {
+                counts.unstaged += 1;
+            }
+            if status.contains(STATUS_WT_NEW) {
+                counts.untracked += 1;
+            }
+        }
+        Ok(counts)
     }
 
     ///
-    /// Function to print some informations about the current git repository.
+    /// Function to count the files whose changes are in the index of a linked working tree, but
+    /// not committed on its own HEAD.
     ///
-    pub fn get_status(&self) {
-        let &Repo(ref repository) = self;
-        let mut to_display = String::new();
-        to_display += &format!("----> {}\n",
-                               Style::new().italic().paint(repository.path().to_str().unwrap()));
-        to_display += &format!("|\t`{}`: {}\n",
-                               Style::new().bold().paint("is bare?"),
-                               repository.is_bare());
-        to_display += &format!("|\t`{}`: {}\n",
-                               Style::new().bold().paint("label?"),
-                               self.get_label());
-        to_display += &format!("|\t`{}`: {:?}\n",
-                               Style::new().bold().paint("state?"),
-                               repository.state());
-        match repository.remotes() {
-            Ok(array_of_remotes) => {
-                to_display += &array_of_remotes.iter()
-                    .map(|remote| format!("|\tExisting remote: {}\n", remote.unwrap()))
-                    .collect::<String>();
+    fn count_worktree_staged_changes(&self) -> Result<usize, Error> {
+        let &Repo(ref repository, _) = self;
+        let head_tree = match self.get_worktree_head_id() {
+            Some(head_id) => Some(repository.find_commit(head_id)?.tree()?),
+            // An unborn branch does not have any commit yet
+            None => None,
+        };
+        let index = repository.index()?;
+        let diff = repository.diff_tree_to_index(head_tree.as_ref(), Some(&index), None)?;
+        Ok(diff.deltas().count())
+    }
+
+    ///
+    /// Function to get the commit of the HEAD of a linked working tree, from the HEAD file of its
+    /// own git directory.
+    ///
+    fn get_worktree_head_id(&self) -> Option<Oid> {
+        let &Repo(ref repository, _) = self;
+        match self.read_worktree_head() {
+            Some(ref head) if head.starts_with(HEAD_REF_PREFIX) => {
+                repository.refname_to_id(head[HEAD_REF_PREFIX.len()..].trim()).ok()
+            }
+            Some(ref head) => Oid::from_str(head.trim()).ok(),
+            None => None,
+        }
+    }
+
+    ///
+    /// Function to read the HEAD file of a linked working tree, in its own git directory.
+    ///
+    fn read_worktree_head(&self) -> Option<String> {
+        self.get_worktree_git_dir().and_then(|git_dir| read_file(&git_dir.join(GIT_HEAD_FILE_NAME)))
+    }
+
+    ///
+    /// Function to get the own git directory of a linked working tree, written in its
+    /// GIT_DIR_NAME file.
+    ///
+    fn get_worktree_git_dir(&self) -> Option<PathBuf> {
+        let &Repo(ref repository, _) = self;
+        repository.workdir().and_then(|workdir| get_gitdir_from_file(&workdir.join(GIT_DIR_NAME)))
+    }
+
+    ///
+    /// Function to get the operation in progress in the repository, like a rebase or a merge,
+    /// with its details.
+    /// The operations of a linked working tree are not the ones of its main repository, and are
+    /// read from its own git directory.
+    ///
+    pub fn get_operation(&self) -> Option<Operation> {
+        let &Repo(ref repository, ref kind) = self;
+        if *kind != RepositoryKind::Worktree {
+            return get_operation(repository, repository.path(), repository.state());
+        }
+        match self.get_worktree_git_dir() {
+            Some(git_dir) => get_operation(repository, &git_dir, get_state_from_git_dir(&git_dir)),
+            None => None,
+        }
+    }
+
+    ///
+    /// Function to get the details of the commit of HEAD - the last commit of the current branch.
+    /// There are no details if HEAD does not point to any commit, like on a branch without any
+    /// commit yet.
+    ///
+    pub fn get_head_commit(&self) -> Option<CommitDetails> {
+        let &Repo(ref repository, ref kind) = self;
+        let head_id = if *kind == RepositoryKind::Worktree {
+            self.get_worktree_head_id()
+        } else {
+            repository.head().ok().and_then(|head| head.target())
+        };
+        let mut commit = match head_id.and_then(|head_id| repository.find_commit(head_id).ok()) {
+            Some(commit) => commit,
+            None => return None,
+        };
+        let id = commit.id().to_string();
+        let short_id = self.get_short_id(commit.id());
+        let author = String::from(commit.author().name().unwrap_or(""));
+        let time = commit.time().seconds();
+        let summary = String::from(commit.summary().unwrap_or(""));
+        Some(CommitDetails {
+            id: id,
+            short_id: short_id,
+            summary: summary,
+            author: author,
+            time: time,
+        })
+    }
+
+    ///
+    /// Function to get the stashes of the repository, from the last one to the first one.
+    ///
+    pub fn get_stashes(&self) -> Vec<StashEntry> {
+        let &Repo(ref repository, _) = self;
+        let reflog = match repository.reflog(STASH_REF_NAME) {
+            Ok(reflog) => reflog,
+            Err(_) => return Vec::new(),
+        };
+        reflog.iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let stash_message =
+                    String::from_utf8_lossy(entry.message_bytes().unwrap_or(&[])).into_owned();
+                let (branch, message) = parse_stash_message(&stash_message);
+                StashEntry {
+                    index: index,
+                    id: self.get_short_id(entry.id_new()),
+                    branch: branch,
+                    message: message,
+                    time: entry.committer().when().seconds(),
+                }
+            })
+            .collect()
+    }
+
+    ///
+    /// Function to count the commits of the current branch that are not on its upstream, and the
+    /// commits of the upstream that are not on the current branch.
+    /// There are no counts if HEAD is not on a local branch, or if this branch does not have any
+    /// upstream.
+    ///
+    pub fn get_upstream_counts(&self) -> Option<UpstreamCounts> {
+        let &Repo(ref repository, ref kind) = self;
+        let branch_name = match self.get_head_state() {
+            HeadState::Branch(branch_name) => branch_name,
+            _ => return None,
+        };
+        let branch = match repository.find_branch(&branch_name, BranchType::Local) {
+            Ok(branch) => branch,
+            Err(_) => return None,
+        };
+        // The current branch of the main repository of a linked working tree is not this one
+        if *kind != RepositoryKind::Worktree && !branch.is_head() {
+            return None;
+        }
+        let upstream = match branch.upstream() {
+            Ok(upstream) => upstream,
+            Err(_) => return None,
+        };
+        let upstream_name = match upstream.name() {
+            Ok(Some(upstream_name)) => String::from(upstream_name),
+            _ => return None,
+        };
+        match (branch.get().target(), upstream.get().target()) {
+            (Some(local_id), Some(upstream_id)) => {
+                repository.graph_ahead_behind(local_id, upstream_id)
+                    .ok()
+                    .map(|(ahead, behind)| {
+                        UpstreamCounts {
+                            upstream: upstream_name,
+                            ahead: ahead,
+                            behind: behind,
+                        }
+                    })
             }
-            Err(_) => to_display += &format!("|\tNo remotes to display!"),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Function to return the name of the current head of the repository, if any: the name of the
+    /// current branch, or the commit of a detached HEAD.
+    ///
+    pub fn get_head_name(&self) -> Option<String> {
+        match self.get_head_state() {
+            HeadState::Branch(name) |
+            HeadState::Detached(name) |
+            HeadState::Unborn(name) => Some(name),
+            HeadState::Broken(_) => None,
+        }
+    }
+
+    ///
+    /// Function to get the current HEAD of the repository: a branch, a detached commit, a branch
+    /// without any commit yet, or a HEAD that cannot be read.
+    /// The head of a linked working tree is not the one of its main repository, and is read from
+    /// its own git directory.
+    ///
+    pub fn get_head_state(&self) -> HeadState {
+        let &Repo(ref repository, ref kind) = self;
+        if *kind == RepositoryKind::Worktree {
+            return self.get_worktree_head_state();
         }
         match repository.head() {
-            Ok(head) => to_display += &format!("|\tHead: {}\n", head.shorthand().unwrap()),
-            Err(_) => to_display += &format!("|\tNo head to display!"),
+            Ok(ref head) if head.is_branch() => {
+                HeadState::Branch(String::from_utf8_lossy(head.shorthand_bytes()).into_owned())
+            }
+            Ok(ref head) => {
+                match head.target() {
+                    Some(head_id) => HeadState::Detached(self.get_short_id(head_id)),
+                    None => HeadState::Broken(String::from("HEAD does not point to a commit")),
+                }
+            }
+            Err(ref error) if error.code() == ErrorCode::UnbornBranch => {
+                let branch = repository.find_reference("HEAD")
+                    .ok()
+                    .and_then(|head| head.symbolic_target().map(get_branch_name));
+                match branch {
+                    Some(branch) => HeadState::Unborn(branch),
+                    None => HeadState::Broken(String::from(error.message())),
+                }
+            }
+            Err(ref error) => HeadState::Broken(String::from(error.message())),
+        }
+    }
+
+    ///
+    /// Function to get the HEAD of a linked working tree, from the HEAD file of its own git
+    /// directory.
+    ///
+    fn get_worktree_head_state(&self) -> HeadState {
+        let &Repo(ref repository, _) = self;
+        match self.read_worktree_head() {
+            Some(ref head) if head.starts_with(HEAD_REF_PREFIX) => {
+                let reference = head[HEAD_REF_PREFIX.len()..].trim();
+                match repository.refname_to_id(reference) {
+                    Ok(_) => HeadState::Branch(get_branch_name(reference)),
+                    Err(ref error) if error.code() == ErrorCode::NotFound => {
+                        HeadState::Unborn(get_branch_name(reference))
+                    }
+                    Err(ref error) => HeadState::Broken(String::from(error.message())),
+                }
+            }
+            Some(ref head) => {
+                match Oid::from_str(head.trim()) {
+                    Ok(head_id) => HeadState::Detached(self.get_short_id(head_id)),
+                    Err(ref error) => HeadState::Broken(String::from(error.message())),
+                }
+            }
+            None => HeadState::Broken(String::from("Cannot read the HEAD file")),
+        }
+    }
+
+    ///
+    /// Function to get the shortest unambiguous abbreviation of the id of an object of the
+    /// repository - or the whole id, if the object cannot be read.
+    ///
+    fn get_short_id(&self, id: Oid) -> String {
+        let &Repo(ref repository, _) = self;
+        repository.find_object(id, None)
+            .ok()
+            .and_then(|object| object.short_id().ok())
+            .and_then(|short_id| short_id.as_str().map(String::from))
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    ///
+    /// Function to get some informations about the current git repository, opened from the given
+    /// local path.
+    ///
+    pub fn get_status(&self, path: &str) -> RepoStatus {
+        let &Repo(ref repository, ref kind) = self;
+        let mut errors = Vec::new();
+        let changes = match self.get_change_counts() {
+            Ok(counts) => Some(counts),
+            Err(error) => {
+                errors.push(format!("Cannot read the changes: {}", error.message()));
+                None
+            }
+        };
+        let label = match changes {
+            Some(ref counts) if counts.is_clean() => RepoLabel::Clean,
+            _ => RepoLabel::Dirty,
+        };
+        let remotes = match repository.remotes() {
+            Ok(array_of_remotes) => {
+                array_of_remotes.iter_bytes()
+                    .map(|remote| String::from_utf8_lossy(remote).into_owned())
+                    .collect()
+            }
+            Err(error) => {
+                errors.push(format!("Cannot read the remotes: {}", error.message()));
+                Vec::new()
+            }
+        };
+        RepoStatus {
+            path: String::from(path),
+            kind: *kind,
+            is_bare: repository.is_bare(),
+            head: Some(self.get_head_state()),
+            head_commit: self.get_head_commit(),
+            label: label,
+            operation: self.get_operation(),
+            remotes: remotes,
+            changes: changes,
+            upstream: self.get_upstream_counts(),
+            stashes: match *kind {
+                RepositoryKind::Worktree => 0,
+                _ => self.get_stashes().len(),
+            },
+            errors: errors,
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_stash_message;
+
+    #[test]
+    fn stash_message_with_branch() {
+        assert_eq!(parse_stash_message("WIP on master: 1a2b3c4 Fix the parser"),
+                   (Some(String::from("master")), String::from("1a2b3c4 Fix the parser")));
+        assert_eq!(parse_stash_message("On feature/age: Keep the old units"),
+                   (Some(String::from("feature/age")), String::from("Keep the old units")));
+    }
+
+    #[test]
+    fn stash_message_on_detached_head_has_no_branch() {
+        assert_eq!(parse_stash_message("WIP on (no branch): 1a2b3c4 Fix the parser"),
+                   (None, String::from("1a2b3c4 Fix the parser")));
+    }
+
+    #[test]
+    fn stash_message_keeps_separators_of_its_own_message() {
+        assert_eq!(parse_stash_message("On master: fix: the parser"),
+                   (Some(String::from("master")), String::from("fix: the parser")));
+    }
 
-        println!("{}", to_display);
+    #[test]
+    fn unknown_stash_message_is_kept_whole() {
+        assert_eq!(parse_stash_message("autostash"), (None, String::from("autostash")));
+        assert_eq!(parse_stash_message("On master without separator"),
+                   (None, String::from("On master without separator")));
+        assert_eq!(parse_stash_message(""), (None, String::new()));
     }
 }
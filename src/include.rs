@@ -0,0 +1,229 @@
+use glob::{MatchOptions, Pattern};
+use scan::resolve_pattern;
+use std::path::{Component, Path};
+
+///
+/// Options used to match a path against an include pattern: wildcards never match a path
+/// separator, but `**` matches any number of directories
+///
+static MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+///
+/// Name of a pattern component that matches any number of directories
+///
+static RECURSIVE_COMPONENT: &'static str = "**";
+
+///
+/// An include pattern, resolved against a scan root:
+/// `pattern`: The glob pattern of the whole path of git repositories
+/// `components`: The pattern of each path component - None for a `**` component
+///
+#[derive(Clone)]
+struct IncludePattern {
+    pattern: Pattern,
+    components: Vec<Option<Pattern>>,
+}
+
+impl IncludePattern {
+    ///
+    /// This function returns a Result type, that contains the pattern of an absolute path, or a
+    /// message if it is not a valid glob
+    ///
+    fn parse(path: &Path) -> Result<Self, String> {
+        let pattern = match Pattern::new(&path.to_string_lossy()) {
+            Ok(pattern) => pattern,
+            Err(error) => {
+                return Err(format!("Invalid include pattern '{}': {}",
+                                   path.to_string_lossy(),
+                                   error))
+            }
+        };
+        let mut components = Vec::new();
+        for component in path.components() {
+            let component = get_component_name(&component);
+            if component == RECURSIVE_COMPONENT {
+                components.push(None);
+                continue;
+            }
+            match Pattern::new(&component) {
+                Ok(component_pattern) => components.push(Some(component_pattern)),
+                Err(error) => {
+                    return Err(format!("Invalid include pattern '{}': component '{}': {}",
+                                       path.to_string_lossy(),
+                                       component,
+                                       error))
+                }
+            }
+        }
+        Ok(IncludePattern {
+            pattern: pattern,
+            components: components,
+        })
+    }
+
+    ///
+    /// This method returns a boolean - true if a git repository matching the pattern may be in the
+    /// given directory, or in one of its subdirectories, else false
+    ///
+    fn may_contain(&self, directory: &Path) -> bool {
+        for (index, component) in directory.components().enumerate() {
+            match self.components.get(index) {
+                Some(&Some(ref component_pattern)) => {
+                    if !component_pattern.matches_with(&get_component_name(&component),
+                                                       &MATCH_OPTIONS) {
+                        return false;
+                    }
+                }
+                // Any directory may contain a git repository matching a `**` component
+                Some(&None) => return true,
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+///
+/// Function to get the name of a path component, as written in a pattern.
+///
+fn get_component_name(component: &Component) -> String {
+    component.as_os_str().to_string_lossy().into_owned()
+}
+
+///
+/// A list of include patterns, resolved against a scan root: git repositories are only searched in
+/// the directories that match one of them.
+/// `patterns`: The valid patterns
+/// `is_empty`: True if no pattern has been given, to find every git repository
+/// `errors`: The messages of the invalid patterns, that are ignored
+///
+/// Relative patterns are relative to the scan root.
+///
+#[derive(Clone)]
+pub struct IncludeRules {
+    patterns: Vec<IncludePattern>,
+    is_empty: bool,
+    errors: Vec<String>,
+}
+
+impl IncludeRules {
+    ///
+    /// The function to instanciate a new IncludeRules structure, from a list of patterns (with
+    /// a leading `~` already expanded) and the scan root - the directories before the first
+    /// wildcard of each pattern are canonicalized, like the scan root
+    ///
+    pub fn new(patterns: &[String], root: &Path) -> Self {
+        let mut rules = IncludeRules {
+            patterns: Vec::new(),
+            is_empty: patterns.is_empty(),
+            errors: Vec::new(),
+        };
+        for pattern in patterns {
+            let pattern = resolve_pattern(&root.join(pattern).to_string_lossy());
+            match IncludePattern::parse(Path::new(&pattern)) {
+                Ok(pattern) => rules.patterns.push(pattern),
+                Err(error) => rules.errors.push(error),
+            }
+        }
+        rules
+    }
+
+    ///
+    /// This method returns the messages of the invalid patterns, that are ignored
+    ///
+    pub fn get_errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    ///
+    /// This method returns a boolean - true if the directory has to be walked into, to find git
+    /// repositories matching a pattern, else false
+    ///
+    pub fn may_contain(&self, directory: &Path) -> bool {
+        self.is_empty || self.patterns.iter().any(|pattern| pattern.may_contain(directory))
+    }
+
+    ///
+    /// This method returns a boolean - true if the git repository matches a pattern, or if there
+    /// is no pattern, else false
+    ///
+    pub fn matches(&self, repository: &Path) -> bool {
+        self.is_empty ||
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.pattern.matches_path_with(repository, &MATCH_OPTIONS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IncludeRules;
+    use std::path::Path;
+
+    fn get_rules(patterns: &[&str]) -> IncludeRules {
+        IncludeRules::new(&patterns.iter()
+                              .map(|pattern| String::from(*pattern))
+                              .collect::<Vec<String>>(),
+                          Path::new("/gyro"))
+    }
+
+    #[test]
+    fn no_pattern_matches_everything() {
+        let rules = get_rules(&[]);
+        assert!(rules.may_contain(Path::new("/gyro/any/directory")));
+        assert!(rules.matches(Path::new("/gyro/any/repository")));
+    }
+
+    #[test]
+    fn relative_pattern_is_relative_to_root() {
+        let rules = get_rules(&["work/*"]);
+        assert!(rules.get_errors().is_empty());
+        assert!(rules.matches(Path::new("/gyro/work/api")));
+        assert!(!rules.matches(Path::new("/gyro/work/oss/api")));
+        assert!(!rules.matches(Path::new("/other/work/api")));
+    }
+
+    #[test]
+    fn directories_are_walked_into_only_if_they_may_contain_a_match() {
+        let rules = get_rules(&["work/*/src"]);
+        assert!(rules.may_contain(Path::new("/gyro")));
+        assert!(rules.may_contain(Path::new("/gyro/work")));
+        assert!(rules.may_contain(Path::new("/gyro/work/api")));
+        assert!(rules.may_contain(Path::new("/gyro/work/api/src")));
+        assert!(!rules.may_contain(Path::new("/gyro/home")));
+        assert!(!rules.may_contain(Path::new("/gyro/work/api/doc")));
+        assert!(!rules.may_contain(Path::new("/gyro/work/api/src/lib")));
+    }
+
+    #[test]
+    fn double_star_matches_any_number_of_directories() {
+        let rules = get_rules(&["/mirrors/**/api"]);
+        assert!(rules.may_contain(Path::new("/mirrors/a/b/c")));
+        assert!(!rules.may_contain(Path::new("/gyro")));
+        assert!(rules.matches(Path::new("/mirrors/api")));
+        assert!(rules.matches(Path::new("/mirrors/a/b/api")));
+        assert!(!rules.matches(Path::new("/mirrors/a/b/web")));
+    }
+
+    #[test]
+    fn invalid_pattern_is_returned_as_an_error() {
+        let rules = get_rules(&["work/[", "home/*"]);
+        assert_eq!(rules.get_errors().len(), 1);
+        assert!(rules.get_errors()[0].contains("work/["));
+        assert!(rules.matches(Path::new("/gyro/home/api")));
+        assert!(!rules.may_contain(Path::new("/gyro/work")));
+    }
+
+    #[test]
+    fn invalid_component_is_returned_as_an_error() {
+        // Valid as a whole path, but its components `[a` and `b]` are not valid globs
+        let rules = get_rules(&["work/[a/b]"]);
+        assert_eq!(rules.get_errors().len(), 1);
+        assert!(rules.get_errors()[0].contains("component"));
+        assert!(!rules.matches(Path::new("/gyro/work/a")));
+    }
+}
@@ -1,14 +1,21 @@
-extern crate ansi_term;
 extern crate chrono;
 extern crate git2;
+extern crate glob;
 extern crate rustc_serialize;
 extern crate toml;
-extern crate walkdir;
 
+pub mod cache;
 pub mod configuration;
+pub mod exclude;
 pub mod file;
 pub mod git;
+pub mod include;
+pub mod mount;
+pub mod operation;
+pub mod rules;
 pub mod scan;
+#[cfg(target_os = "linux")]
+pub mod watch;
 
 use toml::{Encoder, Table};
 
@@ -17,11 +24,53 @@ use toml::{Encoder, Table};
 ///
 pub static GIT_DIR_NAME: &'static str = ".git";
 
+///
+/// Static variable to get the name of the configuration file, in a git directory
+///
+pub static GIT_CONFIG_FILE_NAME: &'static str = "config";
+
+///
+/// Static variable to get the name of the file that contains the current branch, in a git
+/// directory
+///
+pub static GIT_HEAD_FILE_NAME: &'static str = "HEAD";
+
+///
+/// Static variable to get the name of the objects directory, in a git directory
+///
+pub static GIT_OBJECTS_DIR_NAME: &'static str = "objects";
+
+///
+/// Static variable to get the name of the references directory, in a git directory
+///
+pub static GIT_REFS_DIR_NAME: &'static str = "refs";
+
+///
+/// Static variable to get the prefix of a git file, that contains the path of the git directory
+/// of a linked working tree or of a submodule
+///
+pub static GITDIR_FILE_PREFIX: &'static str = "gitdir:";
+
+///
+/// Static variable to get the name of the file that registers the submodules of a git repository
+///
+pub static GITMODULES_FILE_NAME: &'static str = ".gitmodules";
+
+///
+/// Static variable to get the name of the files that contain directories to exclude from a scan
+///
+pub static IGNORE_FILE_NAME: &'static str = ".gyroignore";
+
 ///
 /// Static variable to get the name of the global configuration file
 ///
 pub static CONFIGURATION_FILE_NAME: &'static str = ".gyro";
 
+///
+/// Static variable to get the name of the scan cache file, next to the global configuration file
+///
+pub static SCAN_CACHE_FILE_NAME: &'static str = ".gyro.cache";
+
 ///
 /// Static variable to get the name of the global configuration file copy
 ///
@@ -42,6 +91,11 @@ pub static WATCHED_ENTRY_NAME: &'static str = "watched";
 ///
 pub static IGNORED_ENTRY_NAME: &'static str = "ignored";
 
+///
+/// Static variable to get the entry name of git repositories that are not on the disk anymore
+///
+pub static MISSING_ENTRY_NAME: &'static str = "missing";
+
 ///
 /// Static variable to get the entry name of git repo groups
 ///
@@ -1,21 +1,49 @@
+extern crate ansi_term;
 #[macro_use]
 extern crate clap;
+extern crate chrono;
 extern crate libgyro;
 extern crate rustc_serialize;
 extern crate toml;
 
 pub mod commands;
+pub mod display;
 
 use libgyro::{ConfigurationContent, ConfigurationFile, CONFIGURATION_FILE_NAME, BODY_ENTRY_NAME,
-              IGNORED_ENTRY_NAME, WATCHED_ENTRY_NAME};
-use libgyro::configuration::{ConfigureContent, Entry, EntryCategory};
+              GROUPS_ENTRY_NAME, IGNORED_ENTRY_NAME, MISSING_ENTRY_NAME, SCAN_CACHE_FILE_NAME,
+              WATCHED_ENTRY_NAME};
+use libgyro::cache::ScanCache;
+use libgyro::configuration::{get_entry_key, ConfigureContent, Entry, EntryCategory};
+use libgyro::exclude::ExcludeRules;
 use libgyro::file::{TomlExtension, ConfigurationFileExtension};
 use libgyro::git;
-use libgyro::scan::{find_git_repositories, filter_hidden_repositories};
+use libgyro::git::{RepositoryKind, StatusFilter};
+use libgyro::mount::get_mount_points_of_types;
+use libgyro::rules::{compile_rules, get_rule_category, CompiledRule};
+use libgyro::scan::{canonicalize_path, expand_home_dir, filter_hidden_repositories,
+                    get_repository_kind, resolve_root, FoundRepository, HiddenPolicy, Scanner};
+#[cfg(target_os = "linux")]
+use libgyro::watch::{RepositoryWatcher, WatchEvent};
+#[cfg(target_os = "linux")]
+use chrono::Local;
+use chrono::offset::utc::UTC;
 use rustc_serialize::Encodable;
+use std::collections::HashSet;
 use std::env;
 use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::Arc;
+#[cfg(target_os = "linux")]
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+///
+/// Delay to wait for, in milliseconds, before reading git repositories that just appeared in a
+/// watched directory
+///
+#[cfg(target_os = "linux")]
+static WATCH_SETTLE_DELAY_MS: u64 = 500;
 
 fn get_configuration_file_content(configuration_file_path: &Path,
                                   reset_configuration_file: bool)
@@ -35,6 +63,278 @@ fn get_configuration_file_content(configuration_file_path: &Path,
     }
 }
 
+///
+/// Function to add a git repository found on the hard disk to the configuration file, with a name
+/// that is not used yet, in the category given by the first matching rule (else the default one).
+/// If `is_diff` is true, the git repository is only printed.
+///
+fn add_found_repository(toml_table: &mut ConfigurationContent,
+                        gitrepo: &FoundRepository,
+                        used_names: &mut Vec<String>,
+                        rules: &[CompiledRule],
+                        default_category: EntryCategory,
+                        is_diff: bool,
+                        scan_cache: &mut ScanCache,
+                        configuration_file_path: &Path) {
+    let gitrepo_name = get_entry_key(&gitrepo.path, used_names);
+    used_names.push(gitrepo_name.clone());
+    if gitrepo_name.contains('/') {
+        println!("[WARNING] The name {} is already used - {} is named {} (rename it with \
+                  `{} {} {} --{} <name>`)",
+                 gitrepo.path.split("/").last().unwrap(),
+                 gitrepo.path,
+                 gitrepo_name,
+                 commands::PRG_NAME,
+                 commands::REPO_SUBCMD,
+                 gitrepo_name,
+                 commands::REPO_SUBCMD_ALIAS_FLAG);
+    }
+    // The first matching rule decides the category of the git repository
+    let remote_url = if rules.iter().any(|rule| rule.needs_remote_url()) {
+        git::get_repository_remote_url(&gitrepo.path, gitrepo.kind)
+    } else {
+        None
+    };
+    let rule_category = get_rule_category(rules,
+                                          &gitrepo.path,
+                                          gitrepo.kind,
+                                          remote_url.as_ref().map(|url| url.as_str()));
+    let (gitrepo_category, gitrepo_group) = match rule_category {
+        Some(category) if category == WATCHED_ENTRY_NAME => (EntryCategory::Watched, None),
+        Some(category) if category == IGNORED_ENTRY_NAME => (EntryCategory::Ignored, None),
+        Some(group) => (EntryCategory::Groups, Some(String::from(group))),
+        None => (default_category, None),
+    };
+    if !is_diff {
+        let mut entry = Entry::new(&gitrepo_name, &gitrepo.path);
+        entry.group = gitrepo_group;
+        entry.parent = gitrepo.parent.clone();
+        entry.kind = Some(gitrepo.kind.to_string());
+        entry.identity = git::get_repository_identity(&gitrepo.path, gitrepo.kind, scan_cache);
+        if !gitrepo.aliases.is_empty() {
+            entry.aliases = Some(gitrepo.aliases.clone());
+        }
+        match toml_table.add_entry(&gitrepo_name, &mut entry, &gitrepo_category) {
+            Ok(_) => {
+                match rule_category {
+                    Some(category) => {
+                        println!("{} has been added to {:?}, in {}",
+                                 gitrepo_name,
+                                 configuration_file_path,
+                                 category)
+                    }
+                    None => {
+                        println!("{} has been added to {:?}",
+                                 gitrepo_name,
+                                 configuration_file_path)
+                    }
+                }
+            }
+            Err(error) => println!("[ERROR] {:?}", error),
+        }
+    } else {
+        println!("[DEBUG] Got {} flag !", commands::SCAN_SUBCMD_DIFF_FLAG);
+        match gitrepo.parent {
+            Some(ref parent) => {
+                println!("Found new {} repository: {} (in {}, nested in {})",
+                         gitrepo.kind,
+                         gitrepo_name,
+                         gitrepo.path,
+                         parent)
+            }
+            None => {
+                println!("Found new {} repository: {} (in {})",
+                         gitrepo.kind,
+                         gitrepo_name,
+                         gitrepo.path)
+            }
+        }
+        for alias in &gitrepo.aliases {
+            println!("\talso found as {}", alias);
+        }
+        if let Some(category) = rule_category {
+            println!("\tcategorised as {}", category);
+        }
+    }
+}
+
+///
+/// Function to get the category a missing git repository has been moved from - its group, or the
+/// default category, for entries that do not remember it.
+///
+fn get_category_before_missing(entry: &Entry, default_category: EntryCategory) -> EntryCategory {
+    match entry.category.as_ref().and_then(|category| category.parse::<EntryCategory>().ok()) {
+        Some(category) if category != EntryCategory::Missing => category,
+        _ if entry.group.is_some() => EntryCategory::Groups,
+        _ => default_category,
+    }
+}
+
+///
+/// Function to register again a missing git repository that is back on the hard disk, in the
+/// category it has been moved from.
+/// The git repository is only registered again if its path is still a git repository, with the
+/// same root commit.
+///
+fn restore_missing_repository(toml_table: &mut ConfigurationContent,
+                              gitrepo_name: &str,
+                              gitrepo_path: &str,
+                              default_category: EntryCategory,
+                              scan_cache: &mut ScanCache) {
+    let entry = match toml_table.get_entry(gitrepo_name, &EntryCategory::Missing) {
+        Ok(entry) => entry,
+        Err(error) => {
+            println!("[ERROR] {:?}", error);
+            return;
+        }
+    };
+    let gitrepo_kind = match get_repository_kind(Path::new(gitrepo_path)) {
+        Some(gitrepo_kind) => gitrepo_kind,
+        None => {
+            println!("[WARNING] {} is not a git repository anymore, {} stays in {}",
+                     gitrepo_path,
+                     gitrepo_name,
+                     MISSING_ENTRY_NAME);
+            return;
+        }
+    };
+    if let Some(ref identity) = entry.identity {
+        let is_same_repository = git::get_repository_identity(gitrepo_path,
+                                                               gitrepo_kind,
+                                                               scan_cache)
+            .map_or(false,
+                    |found_identity| git::is_same_identity(identity, &found_identity));
+        if !is_same_repository {
+            println!("[WARNING] {} contains another git repository, {} stays in {}",
+                     gitrepo_path,
+                     gitrepo_name,
+                     MISSING_ENTRY_NAME);
+            return;
+        }
+    }
+    let category = get_category_before_missing(&entry, default_category);
+    match toml_table.transfer_entry(gitrepo_name, &EntryCategory::Missing, &category) {
+        Ok(_) => {
+            println!("{} (in {}) is back, and has been moved to {}",
+                     gitrepo_name,
+                     gitrepo_path,
+                     category.as_str())
+        }
+        Err(error) => println!("[ERROR] {:?}", error),
+    }
+}
+
+///
+/// Function to save the configuration content into the configuration file.
+///
+fn save_configuration_file(toml_table: &ConfigurationContent, configuration_file_path: &Path) {
+    let mut encoding_str = ConfigurationFile::init();
+    match toml_table.encode(&mut encoding_str) {
+        Ok(_) => {
+            match encoding_str.save(configuration_file_path) {
+                Ok(_) => {
+                    println!("The configuration file has been saved in {:?}!",
+                             configuration_file_path)
+                }
+                Err(error) => println!("{}", error),
+            }
+        }
+        Err(error) => println!("{:?}", error),
+    }
+}
+
+///
+/// Function to watch the scanned directories, and to update the configuration file each time a
+/// git repository appears in them (it is added, as during a scan), or disappears (it is moved to
+/// the missing category).
+///
+/// This function only returns if the watched directories cannot be read anymore.
+///
+#[cfg(target_os = "linux")]
+fn watch_repositories(toml_table: &mut ConfigurationContent,
+                      mut watcher: RepositoryWatcher,
+                      hidden_policy: &HiddenPolicy,
+                      used_names: &mut Vec<String>,
+                      rules: &[CompiledRule],
+                      default_category: EntryCategory,
+                      scan_cache: &mut ScanCache,
+                      configuration_file_path: &Path) {
+    println!("Watching the scanned directories for new git repositories... (Ctrl-C to stop)");
+    loop {
+        let events = match watcher.read_events() {
+            Ok(events) => events,
+            Err(error) => {
+                println!("[ERROR] Cannot watch the scanned directories anymore: {}", error);
+                return;
+            }
+        };
+        for error in watcher.take_errors() {
+            println!("[WARNING] {}: {} ({})", error.path, error.kind, error.message);
+        }
+        if events.is_empty() {
+            continue;
+        }
+        // Let the clone, or `git init`, write its git directory before reading it
+        thread::sleep(Duration::from_millis(WATCH_SETTLE_DELAY_MS));
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+        for event in events {
+            match event {
+                WatchEvent::Created(ref gitrepo) => {
+                    if !hidden_policy.is_allowed(Path::new(&gitrepo.path)) {
+                        continue;
+                    }
+                    println!("[{}] New {} repository in {}", now, gitrepo.kind, gitrepo.path);
+                    match toml_table.find_entry_by_path(&gitrepo.path) {
+                        // Missing git repositories that are back are registered again
+                        Some((gitrepo_name, EntryCategory::Missing)) => {
+                            restore_missing_repository(toml_table,
+                                                       &gitrepo_name,
+                                                       &gitrepo.path,
+                                                       default_category,
+                                                       scan_cache)
+                        }
+                        Some(_) => (),
+                        None => {
+                            add_found_repository(toml_table,
+                                                 gitrepo,
+                                                 used_names,
+                                                 rules,
+                                                 default_category,
+                                                 false,
+                                                 scan_cache,
+                                                 configuration_file_path)
+                        }
+                    }
+                }
+                WatchEvent::Removed(ref gitrepo_path) => {
+                    let (gitrepo_name, category) =
+                        match toml_table.find_entry_by_path(gitrepo_path) {
+                            Some((_, EntryCategory::Missing)) |
+                            None => continue,
+                            Some(entry) => entry,
+                        };
+                    println!("[{}] Removed repository {} (was in {})",
+                             now,
+                             gitrepo_name,
+                             gitrepo_path);
+                    match toml_table.transfer_entry(&gitrepo_name,
+                                                    &category,
+                                                    &EntryCategory::Missing) {
+                        Ok(_) => {
+                            println!("{} (in {}) has been moved to {}",
+                                     gitrepo_name,
+                                     gitrepo_path,
+                                     MISSING_ENTRY_NAME)
+                        }
+                        Err(error) => println!("[ERROR] {:?}", error),
+                    }
+                }
+            }
+        }
+        save_configuration_file(toml_table, configuration_file_path);
+    }
+}
+
 fn main() {
 
     // Command line arguments
@@ -42,6 +342,7 @@ fn main() {
 
     if !matches.is_present(commands::RESET_FLAG) && !matches.is_present(commands::SCAN_SUBCMD) &&
        !matches.is_present(commands::STATUS_SUBCMD) &&
+       !matches.is_present(commands::STASHES_SUBCMD) &&
        !matches.is_present(commands::OVERRIDE_SUBCMD) &&
        !matches.is_present(commands::REPO_SUBCMD) {
         println!("{}", matches.usage());
@@ -82,6 +383,10 @@ fn main() {
     let mut vec_path_watched = Vec::new();
     let mut vec_ignored = Vec::new();
     let mut vec_path_ignored = Vec::new();
+    let mut vec_missing = Vec::new();
+    let mut vec_path_missing = Vec::new();
+    let mut vec_grouped = Vec::new();
+    let mut vec_path_grouped = Vec::new();
     // Store watched and ignored git path repositories, from the configuration file
     for (key, value) in toml_table.iter() {
         let category_separator_index = key.find('.');
@@ -90,17 +395,26 @@ fn main() {
         }
         let key_path: String =
             value.as_table().unwrap().get("path").unwrap().as_str().unwrap().to_owned();
+        // Entries stored before kinds of git repositories were recorded are normal ones
+        let key_kind = match value.as_table().unwrap().get("kind").and_then(|kind| kind.as_str()) {
+            Some(kind) => kind.parse::<RepositoryKind>().unwrap_or(RepositoryKind::Normal),
+            None => RepositoryKind::Normal,
+        };
         unsafe {
             let based_key = key.slice_unchecked(category_separator_index.unwrap() + 1, key.len())
                 .to_owned();
             if key.starts_with(WATCHED_ENTRY_NAME) && (key != WATCHED_ENTRY_NAME) {
                 vec_watched.push(based_key);
-                vec_path_watched.push(key_path);
-            } else {
-                if key.starts_with(IGNORED_ENTRY_NAME) && (key != IGNORED_ENTRY_NAME) {
-                    vec_ignored.push(based_key);
-                    vec_path_ignored.push(key_path);
-                }
+                vec_path_watched.push((key_path, key_kind));
+            } else if key.starts_with(IGNORED_ENTRY_NAME) && (key != IGNORED_ENTRY_NAME) {
+                vec_ignored.push(based_key);
+                vec_path_ignored.push((key_path, key_kind));
+            } else if key.starts_with(MISSING_ENTRY_NAME) && (key != MISSING_ENTRY_NAME) {
+                vec_missing.push(based_key);
+                vec_path_missing.push((key_path, key_kind));
+            } else if key.starts_with(GROUPS_ENTRY_NAME) && (key != GROUPS_ENTRY_NAME) {
+                vec_grouped.push(based_key);
+                vec_path_grouped.push((key_path, key_kind));
             }
         }
     }
@@ -109,26 +423,60 @@ fn main() {
     if let Some(ref matches) = matches.subcommand_matches(commands::REPO_SUBCMD) {
         println!("[DEBUG] Got {} command !", commands::REPO_SUBCMD);
         let repository_name = matches.value_of(commands::REPO_SUBCMD_NAME_FLAG).unwrap().to_owned();
-        if !vec_watched.contains(&repository_name) && !vec_ignored.contains(&repository_name) {
+        let old_category = if vec_watched.contains(&repository_name) {
+            EntryCategory::Watched
+        } else if vec_ignored.contains(&repository_name) {
+            EntryCategory::Ignored
+        } else if vec_grouped.contains(&repository_name) {
+            EntryCategory::Groups
+        } else if vec_missing.contains(&repository_name) {
+            EntryCategory::Missing
+        } else {
             println!("[WARNING] Git local repository {} not found! Have you scanned recently \
                       your hard drive ?",
                      repository_name);
             exit(1);
-        }
-        let mut old_category = EntryCategory::Watched;
-        let mut new_category = EntryCategory::Ignored;
-        if vec_watched.contains(&repository_name) {
-            println!("The git local repository {} is in {}!",
-                     repository_name,
-                     WATCHED_ENTRY_NAME);
-        } else {
-            println!("The git local repository {} is in {}!",
-                     repository_name,
-                     IGNORED_ENTRY_NAME);
-            old_category = EntryCategory::Ignored;
-            new_category = EntryCategory::Watched;
+        };
+        println!("The git local repository {} is in {}!",
+                 repository_name,
+                 old_category.as_str());
+        if let Some(alias) = matches.value_of(commands::REPO_SUBCMD_ALIAS_FLAG) {
+            let alias = alias.trim();
+            if vec_watched.iter()
+                .chain(vec_ignored.iter())
+                .chain(vec_grouped.iter())
+                .chain(vec_missing.iter())
+                .any(|name| name == alias) {
+                println!("[ERROR] The name {} is already used by another repository", alias);
+                exit(1);
+            }
+            match toml_table.rename_entry(&repository_name, alias, &old_category) {
+                Ok(()) => {
+                    println!("The local git repository '{}' is now named '{}'!",
+                             repository_name,
+                             alias)
+                }
+                Err(error) => {
+                    println!("[ERROR] Error renaming the local git repository '{}': {}",
+                             repository_name,
+                             error)
+                }
+            }
         }
         if matches.is_present(commands::REPO_SUBCMD_MOVE_FLAG) {
+            let new_category = match old_category {
+                EntryCategory::Watched => EntryCategory::Ignored,
+                EntryCategory::Ignored => EntryCategory::Watched,
+                _ => {
+                    println!("[ERROR] The local git repository '{}' is in {}: only {} and {} \
+                              repositories can be moved",
+                             repository_name,
+                             old_category.as_str(),
+                             WATCHED_ENTRY_NAME,
+                             IGNORED_ENTRY_NAME);
+                    exit(1);
+                }
+            };
             match toml_table.transfer_entry(&repository_name, &old_category, &new_category) {
                 Ok(()) => println!("[DEBUG] The local git repository '{}' has been transfered from `{:?}` to `{:?}`!", repository_name, old_category, new_category),
                 Err(error) => {
@@ -140,25 +488,134 @@ fn main() {
         }
     }
 
+    // Watched git repositories, and the ones of groups, are the ones to follow
+    let vec_path_followed = vec_path_watched.iter()
+        .chain(vec_path_grouped.iter())
+        .cloned()
+        .collect::<Vec<(String, RepositoryKind)>>();
+
     // Get statuses
     if matches.is_present(commands::STATUS_SUBCMD) {
         println!("[DEBUG] Got {} command !", commands::STATUS_SUBCMD);
-        let get_only_dirty = !matches.subcommand_matches(commands::STATUS_SUBCMD)
-            .unwrap()
-            .is_present(commands::STATUS_SUBCMD_CLEAN_FLAG);
-        let get_only_clean = !matches.subcommand_matches(commands::STATUS_SUBCMD)
-            .unwrap()
-            .is_present(commands::STATUS_SUBCMD_DIRTY_FLAG);
-        git::get_statuses_from(&vec_path_watched, get_only_clean, get_only_dirty);
+        let status_matches = matches.subcommand_matches(commands::STATUS_SUBCMD).unwrap();
+        let status_filter = StatusFilter::new()
+            .clean(status_matches.is_present(commands::STATUS_SUBCMD_CLEAN_FLAG))
+            .dirty(status_matches.is_present(commands::STATUS_SUBCMD_DIRTY_FLAG))
+            .unpushed(status_matches.is_present(commands::STATUS_SUBCMD_UNPUSHED_FLAG))
+            .behind(status_matches.is_present(commands::STATUS_SUBCMD_BEHIND_FLAG))
+            .in_progress(status_matches.is_present(commands::STATUS_SUBCMD_IN_PROGRESS_FLAG));
+        for status in git::get_statuses_from(&vec_path_followed, &status_filter) {
+            display::print_repository_status(&status);
+        }
+    }
+
+    // List stashes
+    if let Some(ref stashes_matches) = matches.subcommand_matches(commands::STASHES_SUBCMD) {
+        println!("[DEBUG] Got {} command !", commands::STASHES_SUBCMD);
+        let created_before = stashes_matches.value_of(commands::STASHES_SUBCMD_OLDER_THAN_FLAG)
+            .and_then(commands::parse_age)
+            .map(|age| UTC::now().timestamp() - age);
+        let repositories_stashes = git::get_stashes_from(&vec_path_followed, created_before);
+        if repositories_stashes.is_empty() {
+            println!("No stash found in the watched git repositories!");
+        }
+        for &(ref path, ref stashes) in &repositories_stashes {
+            display::print_stashes(path, stashes);
+        }
     }
 
     if matches.is_present(commands::SCAN_SUBCMD) {
         println!("[DEBUG] Got {} command !", commands::SCAN_SUBCMD);
+        let scan_matches = matches.subcommand_matches(commands::SCAN_SUBCMD).unwrap();
+        let configuration_body = match toml_table.get_body() {
+            Ok(body) => body,
+            Err(error) => {
+                println!("[ERROR] {}", error);
+                exit(1);
+            }
+        };
+        // Get the directories to scan: the ones given as arguments, else the ones from the
+        // configuration file, else the user home directory
+        let root_values = scan_matches.values_of(commands::SCAN_SUBCMD_ROOT_FLAG);
+        let mut scan_roots: Vec<String> = match root_values {
+            Some(roots) => roots.map(String::from).collect(),
+            None => configuration_body.get_roots(),
+        };
+        if scan_roots.is_empty() {
+            scan_roots.push(String::from("~"));
+        }
+        // Relative scan roots would give relative paths of git repositories
+        let scan_roots = scan_roots.iter()
+            .filter_map(|root| match resolve_root(root) {
+                Ok(root) => Some(root),
+                Err(error) => {
+                    println!("[ERROR] Cannot scan {}: {}", root, error);
+                    None
+                }
+            })
+            .collect::<Vec<PathBuf>>();
+        if scan_roots.is_empty() {
+            exit(1);
+        }
+        let jobs = match scan_matches.value_of(commands::SCAN_SUBCMD_JOBS_FLAG) {
+            Some(jobs) => jobs.parse::<usize>().unwrap(),
+            None => configuration_body.get_jobs(),
+        };
+        // Get the scan cache, next to the configuration file, unless a full scan is requested
+        let scan_cache_path = configuration_file_path.with_file_name(SCAN_CACHE_FILE_NAME);
+        let scan_cache = if scan_matches.is_present(commands::SCAN_SUBCMD_FULL_FLAG) {
+            ScanCache::new()
+        } else {
+            ScanCache::load(&scan_cache_path).unwrap_or_else(ScanCache::new)
+        };
+        let max_depth = match scan_matches.value_of(commands::SCAN_SUBCMD_MAX_DEPTH_FLAG) {
+            Some(max_depth) => max_depth.parse::<usize>().ok(),
+            None => configuration_body.max_depth,
+        };
+        let exclude_rules = ExcludeRules::new(&configuration_body.get_exclude_patterns());
+        display::print_warnings(exclude_rules.get_errors());
+        let (skipped_mounts, mount_errors) =
+            get_mount_points_of_types(&configuration_body.get_skip_mount_types());
+        display::print_warnings(&mount_errors);
+        let mut scanner = scan_roots.iter()
+            .fold(Scanner::new(), |scanner, root| scanner.root(root.clone()))
+            .exclude(exclude_rules)
+            .nested(configuration_body.get_nested() ||
+                    scan_matches.is_present(commands::SCAN_SUBCMD_NESTED_FLAG))
+            .jobs(jobs)
+            .one_file_system(configuration_body.get_one_file_system() ||
+                             scan_matches.is_present(commands::SCAN_SUBCMD_ONE_FILE_SYSTEM_FLAG))
+            .skip_mounts(skipped_mounts)
+            .skip_mounts(configuration_body.get_skip_mounts()
+                .iter()
+                .map(|mount_point| expand_home_dir(mount_point))
+                .collect())
+            .min_depth(match scan_matches.value_of(commands::SCAN_SUBCMD_MIN_DEPTH_FLAG) {
+                Some(min_depth) => min_depth.parse::<usize>().unwrap(),
+                None => configuration_body.min_depth.unwrap_or(0),
+            })
+            .include(&match scan_matches.values_of(commands::SCAN_SUBCMD_INCLUDE_FLAG) {
+                Some(patterns) => patterns.map(String::from).collect(),
+                None => configuration_body.get_include_patterns(),
+            })
+            .cache(scan_cache)
+            .keep_positions(scan_matches.is_present(commands::SCAN_SUBCMD_WATCH_FLAG))
+            .progress(Arc::new(display::ScanPrinter::new()));
+        if let Some(max_depth) = max_depth {
+            scanner = scanner.max_depth(max_depth);
+        }
         // Get local git path directories
-        let mut gitpath_directories: Vec<String> = Vec::new();
-        find_git_repositories(&mut gitpath_directories, &env::home_dir().unwrap());
+        let scan_report = scanner.scan();
+        display::print_scan_summary(&scan_report,
+                                    scan_matches.is_present(commands::SCAN_SUBCMD_VERBOSE_FLAG));
         // Get git repositories that are not in an hidden path
-        let filtered_git_repositories = filter_hidden_repositories(&gitpath_directories);
+        let hidden_policy = HiddenPolicy::new(&configuration_body.get_hidden_allow_patterns(),
+                                              &configuration_body.get_hidden_deny_patterns())
+            .include_all(configuration_body.get_hidden() ||
+                         scan_matches.is_present(commands::SCAN_SUBCMD_HIDDEN_FLAG));
+        display::print_warnings(hidden_policy.get_errors());
+        let filtered_git_repositories = filter_hidden_repositories(&scan_report.repositories,
+                                                                   &hidden_policy);
 
         // Compiler error when using pattern matching - TODO
         let entry_category = if default_category_storage == WATCHED_ENTRY_NAME {
@@ -167,54 +624,260 @@ fn main() {
             EntryCategory::Ignored
         };
 
-        // Filter local git repository, and add them
+        let is_diff = scan_matches.is_present(commands::SCAN_SUBCMD_DIFF_FLAG);
+        let registered_repositories = vec_watched.iter()
+            .zip(vec_path_watched.iter())
+            .map(|(name, path)| (name, path, EntryCategory::Watched))
+            .chain(vec_ignored.iter()
+                .zip(vec_path_ignored.iter())
+                .map(|(name, path)| (name, path, EntryCategory::Ignored)))
+            .chain(vec_grouped.iter()
+                .zip(vec_path_grouped.iter())
+                .map(|(name, path)| (name, path, EntryCategory::Groups)))
+            .chain(vec_missing.iter()
+                .zip(vec_path_missing.iter())
+                .map(|(name, path)| (name, path, EntryCategory::Missing)))
+            .map(|(name, &(ref path, kind), category)| (name.clone(), path.clone(), kind, category))
+            .collect::<Vec<(String, String, RepositoryKind, EntryCategory)>>();
+        // Registered paths may have been written through a symbolic link, unlike scan roots, and
+        // a git repository may have been registered through one of its aliases
+        let registered_paths = registered_repositories.iter()
+            .flat_map(|&(ref name, ref path, _, ref category)| {
+                let aliases = toml_table.get_entry(name, category)
+                    .ok()
+                    .and_then(|entry| entry.aliases)
+                    .unwrap_or_default();
+                aliases.into_iter().chain(Some(path.clone()))
+            })
+            .map(|path| canonicalize_path(Path::new(&path)))
+            .collect::<HashSet<PathBuf>>();
+        let is_registered = |gitrepo: &FoundRepository| {
+            Some(&gitrepo.path)
+                .into_iter()
+                .chain(gitrepo.aliases.iter())
+                .any(|path| registered_paths.contains(&canonicalize_path(Path::new(path))))
+        };
+
+        // Compare registered git repositories against the hard disk
+        let mut vanished_repositories = Vec::new();
+        for (gitrepo_name, gitrepo_path, gitrepo_kind, category) in registered_repositories {
+            if get_repository_kind(Path::new(&gitrepo_path)).is_none() {
+                vanished_repositories.push((gitrepo_name, gitrepo_path, category));
+                continue;
+            }
+            // Git repositories registered before identities were recorded get one
+            if is_diff || category == EntryCategory::Missing {
+                continue;
+            }
+            if let Ok(mut entry) = toml_table.get_entry(&gitrepo_name, &category) {
+                if entry.identity.is_none() {
+                    entry.identity = git::get_repository_identity(&gitrepo_path,
+                                                                  gitrepo_kind,
+                                                                  scanner.get_cache_mut());
+                    if entry.identity.is_some() {
+                        if let Err(error) = toml_table.update_entry(&gitrepo_name,
+                                                                    &mut entry,
+                                                                    &category) {
+                            println!("[ERROR] {:?}", error);
+                        }
+                    }
+                }
+            }
+        }
+
+        // New git repositories with the identity of a vanished one have been moved
+        let mut moved_paths = Vec::new();
         for gitrepo in &filtered_git_repositories {
-            let gitrepo_name = gitrepo.split("/").last().unwrap();
-            let gitrepo_name_s = String::from(gitrepo_name);
-            if !(vec_watched.contains(&gitrepo_name_s) || vec_ignored.contains(&gitrepo_name_s)) {
-                if !(matches.subcommand_matches(commands::SCAN_SUBCMD)
-                    .unwrap()
-                    .is_present(commands::SCAN_SUBCMD_DIFF_FLAG)) {
-                    match toml_table.add_entry(gitrepo_name,
-                                               &mut Entry::new(gitrepo_name, gitrepo),
-                                               &entry_category) {
+            if vanished_repositories.is_empty() {
+                break;
+            }
+            if is_registered(gitrepo) {
+                continue;
+            }
+            let identity = match git::get_repository_identity(&gitrepo.path,
+                                                              gitrepo.kind,
+                                                              scanner.get_cache_mut()) {
+                Some(identity) => identity,
+                None => continue,
+            };
+            // A vanished git repository with the same remote url too is preferred
+            let vanished_identities = vanished_repositories.iter()
+                .map(|&(ref name, _, ref category)| {
+                    toml_table.get_entry(name, category).ok().and_then(|entry| entry.identity)
+                })
+                .collect::<Vec<Option<String>>>();
+            let position = vanished_identities.iter()
+                .position(|vanished_identity| vanished_identity.as_ref() == Some(&identity))
+                .or_else(|| {
+                    vanished_identities.iter().position(|vanished_identity| {
+                        vanished_identity.as_ref()
+                            .map_or(false, |vanished_identity| {
+                                git::is_same_identity(vanished_identity, &identity)
+                            })
+                    })
+                });
+            let (gitrepo_name, gitrepo_path, category) = match position {
+                Some(position) => vanished_repositories.remove(position),
+                None => continue,
+            };
+            moved_paths.push(gitrepo.path.clone());
+            if is_diff {
+                println!("Found moved {} repository: {} (from {} to {})",
+                         gitrepo.kind,
+                         gitrepo_name,
+                         gitrepo_path,
+                         gitrepo.path);
+                continue;
+            }
+            let mut entry = match toml_table.get_entry(&gitrepo_name, &category) {
+                Ok(entry) => entry,
+                Err(error) => {
+                    println!("[ERROR] {:?}", error);
+                    continue;
+                }
+            };
+            entry.path = gitrepo.path.clone();
+            entry.parent = gitrepo.parent.clone();
+            entry.kind = Some(gitrepo.kind.to_string());
+            entry.identity = Some(identity);
+            entry.aliases = if gitrepo.aliases.is_empty() {
+                None
+            } else {
+                Some(gitrepo.aliases.clone())
+            };
+            let category_before_missing = get_category_before_missing(&entry, entry_category);
+            let updated = toml_table.update_entry(&gitrepo_name, &mut entry, &category)
+                .and_then(|_| if category == EntryCategory::Missing {
+                    toml_table.transfer_entry(&gitrepo_name, &category, &category_before_missing)
+                } else {
+                    Ok(())
+                });
+            match updated {
+                Ok(_) => {
+                    println!("{} has been moved from {} to {}",
+                             gitrepo_name,
+                             gitrepo_path,
+                             gitrepo.path)
+                }
+                Err(error) => println!("[ERROR] {:?}", error),
+            }
+        }
+
+        for (gitrepo_name, gitrepo_path, category) in vanished_repositories {
+            if category == EntryCategory::Missing {
+                continue;
+            }
+            match scan_matches.value_of(commands::SCAN_SUBCMD_PRUNE_FLAG) {
+                Some(action) if action == commands::SCAN_SUBCMD_PRUNE_REMOVE_VALUE => {
+                    match toml_table.remove_entry(&gitrepo_name, &category) {
                         Ok(_) => {
-                            match entry_category {
-                                EntryCategory::Watched => vec_watched.push(gitrepo_name_s),
-                                EntryCategory::Ignored => vec_ignored.push(gitrepo_name_s),
-                                EntryCategory::Groups => (),
-                            }
-                            println!("{} has been added to {:?}",
+                            vec_watched.retain(|name| *name != gitrepo_name);
+                            vec_ignored.retain(|name| *name != gitrepo_name);
+                            println!("{} (in {}) has been removed from {:?}",
                                      gitrepo_name,
+                                     gitrepo_path,
                                      configuration_file_path);
                         }
                         Err(error) => println!("[ERROR] {:?}", error),
                     }
-                } else {
-                    println!("[DEBUG] Got {} flag !", commands::SCAN_SUBCMD_DIFF_FLAG);
-                    println!("Found new repository: {} (in {})", gitrepo_name_s, gitrepo);
                 }
+                Some(_) => {
+                    match toml_table.transfer_entry(&gitrepo_name,
+                                                    &category,
+                                                    &EntryCategory::Missing) {
+                        Ok(_) => {
+                            println!("{} (in {}) has been moved to {}",
+                                     gitrepo_name,
+                                     gitrepo_path,
+                                     MISSING_ENTRY_NAME)
+                        }
+                        Err(error) => println!("[ERROR] {:?}", error),
+                    }
+                }
+                None => {
+                    println!("[WARNING] Missing {:?} repository: {} (was in {})",
+                             category,
+                             gitrepo_name,
+                             gitrepo_path)
+                }
+            }
+        }
+        // Missing git repositories that are back on the hard disk are registered again
+        for (gitrepo_name, &(ref gitrepo_path, _)) in vec_missing.iter()
+            .zip(vec_path_missing.iter()) {
+            if !Path::new(gitrepo_path).exists() || is_diff {
+                continue;
             }
+            restore_missing_repository(&mut toml_table,
+                                       gitrepo_name,
+                                       gitrepo_path,
+                                       entry_category,
+                                       scanner.get_cache_mut());
         }
 
+        // Filter local git repository, and add them with a name that is not used yet
+        let mut used_names = vec_watched.iter()
+            .chain(vec_ignored.iter())
+            .chain(vec_grouped.iter())
+            .chain(vec_missing.iter())
+            .cloned()
+            .collect::<Vec<String>>();
+        let (rules, rule_errors) = compile_rules(&configuration_body.get_rules());
+        display::print_warnings(&rule_errors);
+        for gitrepo in &filtered_git_repositories {
+            if moved_paths.contains(&gitrepo.path) || is_registered(gitrepo) {
+                continue;
+            }
+            add_found_repository(&mut toml_table,
+                                 gitrepo,
+                                 &mut used_names,
+                                 &rules,
+                                 entry_category,
+                                 is_diff,
+                                 scanner.get_cache_mut(),
+                                 &configuration_file_path);
+        }
+        if let Err(error) = scanner.get_cache().save(&scan_cache_path) {
+            println!("[WARNING] Cannot save the scan cache in {:?}: {}",
+                     scan_cache_path,
+                     error);
+        }
+
+        // Keep the configuration file up to date with the git repositories that appear, or
+        // disappear, in the scanned directories
+        if scan_matches.is_present(commands::SCAN_SUBCMD_WATCH_FLAG) {
+            #[cfg(target_os = "linux")]
+            {
+                let watcher = match RepositoryWatcher::new(&scan_report) {
+                    Ok(watcher) => watcher,
+                    Err(error) => {
+                        println!("[ERROR] Cannot watch the scanned directories: {}", error);
+                        exit(1);
+                    }
+                };
+                save_configuration_file(&toml_table, &configuration_file_path);
+                watch_repositories(&mut toml_table,
+                                   watcher,
+                                   &hidden_policy,
+                                   &mut used_names,
+                                   &rules,
+                                   entry_category,
+                                   scanner.get_cache_mut(),
+                                   &configuration_file_path);
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                println!("[ERROR] The --{} flag is only available on Linux",
+                         commands::SCAN_SUBCMD_WATCH_FLAG);
+                exit(1);
+            }
+        }
     }
 
     // Save part
     if matches.is_present(commands::SAVE_FLAG) {
         println!("[DEBUG] Got {} flag !", commands::SAVE_FLAG);
-        let mut encoding_str = ConfigurationFile::init();
-        match toml_table.encode(&mut encoding_str) {
-            Ok(_) => {
-                match encoding_str.save(Path::new(configuration_file_path.as_path())) { 
-                    Ok(_) => {
-                        println!("The configuration file has been saved in {:?}!",
-                                 configuration_file_path)
-                    }
-                    Err(error) => println!("{}", error),
-                }
-            }
-            Err(error) => println!("{:?}", error),
-        }
+        save_configuration_file(&toml_table, &configuration_file_path);
     }
 
 }
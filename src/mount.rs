@@ -0,0 +1,136 @@
+use glob::Pattern;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+
+///
+/// The file that lists the mounted file systems, on Linux
+///
+static MOUNTS_FILE_PATH: &'static str = "/proc/self/mounts";
+
+///
+/// A mounted file system:
+/// `path`: The directory where the file system is mounted
+/// `fs_type`: The type of the file system, like `ext4`, `nfs` or `fuse.sshfs`
+///
+#[derive(Clone, Debug)]
+pub struct MountPoint {
+    pub path: PathBuf,
+    pub fs_type: String,
+}
+
+///
+/// Function to decode a field of the mounts file, where spaces, tabulations, new lines and
+/// backslashes are written as octal escapes (like `\040`).
+///
+/// Fields are decoded as bytes, as paths do not have to be valid UTF-8.
+///
+fn decode_mount_field(field: &[u8]) -> OsString {
+    let mut decoded = Vec::new();
+    let mut bytes = field.iter().cloned();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            decoded.push(byte);
+            continue;
+        }
+        let octal = bytes.by_ref().take(3).collect::<Vec<u8>>();
+        match String::from_utf8(octal.clone())
+            .ok()
+            .and_then(|octal| u8::from_str_radix(&octal, 8).ok()) {
+            Some(decoded_byte) => decoded.push(decoded_byte),
+            None => {
+                decoded.push(byte);
+                decoded.extend(octal);
+            }
+        }
+    }
+    OsString::from_vec(decoded)
+}
+
+///
+/// Function to get the mounted file systems.
+///
+/// This function returns an empty vector if the mounts file cannot be read, like on systems other
+/// than Linux.
+///
+pub fn get_mount_points() -> Vec<MountPoint> {
+    let file = match File::open(MOUNTS_FILE_PATH) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    BufReader::new(file)
+        .split(b'\n')
+        .filter_map(|line| line.ok())
+        .filter_map(|line| {
+            let mut fields = line.split(|byte| *byte == b' ' || *byte == b'\t')
+                .filter(|field| !field.is_empty())
+                .skip(1);
+            match (fields.next(), fields.next()) {
+                (Some(path), Some(fs_type)) => {
+                    Some(MountPoint {
+                        path: PathBuf::from(decode_mount_field(path)),
+                        fs_type: String::from_utf8_lossy(fs_type).into_owned(),
+                    })
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+///
+/// Function to get the directories where a file system of one of the given types is mounted.
+///
+/// Types are glob patterns, like `fuse.*`.
+/// This function also returns the messages of the invalid patterns, that are ignored.
+///
+pub fn get_mount_points_of_types(fs_types: &[String]) -> (Vec<PathBuf>, Vec<String>) {
+    let mut patterns = Vec::new();
+    let mut errors = Vec::new();
+    for fs_type in fs_types {
+        match Pattern::new(fs_type) {
+            Ok(pattern) => patterns.push(pattern),
+            Err(error) => {
+                errors.push(format!("Invalid mount type pattern '{}': {}", fs_type, error))
+            }
+        }
+    }
+    if patterns.is_empty() {
+        return (Vec::new(), errors);
+    }
+    let mount_points = get_mount_points()
+        .into_iter()
+        .filter(|mount_point| patterns.iter().any(|pattern| pattern.matches(&mount_point.fs_type)))
+        .map(|mount_point| mount_point.path)
+        .collect();
+    (mount_points, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_mount_field;
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    #[test]
+    fn octal_escapes_are_decoded() {
+        assert_eq!(decode_mount_field(b"/mnt/my\\040disk"), OsString::from("/mnt/my disk"));
+        assert_eq!(decode_mount_field(b"/mnt/a\\011b\\134c"), OsString::from("/mnt/a\tb\\c"));
+    }
+
+    #[test]
+    fn non_ascii_paths_are_decoded_as_bytes() {
+        assert_eq!(decode_mount_field("/mnt/café".as_bytes()), OsString::from("/mnt/café"));
+        assert_eq!(decode_mount_field(b"/mnt/caf\\303\\251"), OsString::from("/mnt/café"));
+        assert_eq!(decode_mount_field(b"/mnt/\\377"),
+                   OsString::from_vec(vec![b'/', b'm', b'n', b't', b'/', 0xff]));
+    }
+
+    #[test]
+    fn invalid_escape_is_kept() {
+        assert_eq!(decode_mount_field(b"/mnt/a\\x"), OsString::from("/mnt/a\\x"));
+        assert_eq!(decode_mount_field(b"/mnt/a\\9zz"), OsString::from("/mnt/a\\9zz"));
+    }
+}
@@ -0,0 +1,498 @@
+use git2::{Oid, Repository, RepositoryState};
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+///
+/// Directory of an interactive rebase, or of a rebase with the merge backend, in a git directory
+///
+static REBASE_MERGE_DIR_NAME: &'static str = "rebase-merge";
+
+///
+/// Directory of a rebase with the apply backend, or of `git am`, in a git directory
+///
+static REBASE_APPLY_DIR_NAME: &'static str = "rebase-apply";
+
+///
+/// Files of the current step, and of the number of steps, of a rebase with the merge backend
+///
+static REBASE_MERGE_STEP_FILE_NAME: &'static str = "msgnum";
+static REBASE_MERGE_END_FILE_NAME: &'static str = "end";
+
+///
+/// File that only exists during an interactive rebase, in the rebase-merge directory
+///
+static REBASE_INTERACTIVE_FILE_NAME: &'static str = "interactive";
+
+///
+/// Files of the current step, and of the number of steps, of a rebase with the apply backend
+///
+static REBASE_APPLY_STEP_FILE_NAME: &'static str = "next";
+static REBASE_APPLY_END_FILE_NAME: &'static str = "last";
+
+///
+/// Files that only exist during a rebase, or during `git am`, in the rebase-apply directory
+///
+static REBASE_APPLY_REBASING_FILE_NAME: &'static str = "rebasing";
+static REBASE_APPLY_APPLYING_FILE_NAME: &'static str = "applying";
+
+///
+/// Files of the commits being merged, cherry-picked or reverted, in a git directory
+///
+static MERGE_HEAD_FILE_NAME: &'static str = "MERGE_HEAD";
+static CHERRY_PICK_HEAD_FILE_NAME: &'static str = "CHERRY_PICK_HEAD";
+static REVERT_HEAD_FILE_NAME: &'static str = "REVERT_HEAD";
+
+///
+/// File of the commits left to cherry-pick or to revert, in a git directory
+///
+static SEQUENCER_TODO_FILE_NAME: &'static str = "sequencer/todo";
+
+///
+/// File that exists during a bisection, and directory of its references, in a git directory
+///
+static BISECT_LOG_FILE_NAME: &'static str = "BISECT_LOG";
+static BISECT_REFS_DIR_NAME: &'static str = "refs/bisect";
+
+///
+/// An operation that has been started in a git repository, and not finished yet:
+/// `Merge` is corresponding to a merge, with the short id of the merged commit
+/// `Rebase` is corresponding to a rebase (interactive or not), with its current step and its
+/// number of steps
+/// `ApplyMailbox` is corresponding to `git am`, with its current patch and its number of patches
+/// `CherryPick` is corresponding to a cherry-pick, with the short id of the cherry-picked commit,
+/// and if other commits are left to cherry-pick
+/// `Revert` is corresponding to a revert, with the short id of the reverted commit, and if other
+/// commits are left to revert
+/// `Bisect` is corresponding to a bisection, with the number of good and bad commits marked
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Operation {
+    Merge { head: Option<String> },
+    Rebase {
+        interactive: bool,
+        step: Option<(usize, usize)>,
+    },
+    ApplyMailbox { step: Option<(usize, usize)> },
+    CherryPick {
+        head: Option<String>,
+        sequence: bool,
+    },
+    Revert {
+        head: Option<String>,
+        sequence: bool,
+    },
+    Bisect { good: usize, bad: usize },
+}
+
+impl Operation {
+    ///
+    /// This method returns the label of the operation, as printed in statuses
+    ///
+    pub fn as_label(&self) -> &'static str {
+        match *self {
+            Operation::Merge { .. } => "MERGING",
+            Operation::Rebase { .. } => "REBASING",
+            Operation::ApplyMailbox { .. } => "APPLYING",
+            Operation::CherryPick { .. } => "CHERRY-PICKING",
+            Operation::Revert { .. } => "REVERTING",
+            Operation::Bisect { .. } => "BISECTING",
+        }
+    }
+
+    ///
+    /// This method returns the git command that continues the operation
+    ///
+    pub fn get_continue_command(&self) -> &'static str {
+        match *self {
+            Operation::Merge { .. } => "git merge --continue",
+            Operation::Rebase { .. } => "git rebase --continue",
+            Operation::ApplyMailbox { .. } => "git am --continue",
+            Operation::CherryPick { .. } => "git cherry-pick --continue",
+            Operation::Revert { .. } => "git revert --continue",
+            Operation::Bisect { .. } => "git bisect good|bad",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    ///
+    /// Function that format the operation to display
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operation::Merge { ref head } => {
+                write!(f, "merge")?;
+                if let Some(ref head) = *head {
+                    write!(f, " of {}", head)?;
+                }
+            }
+            Operation::Rebase { interactive, step } => {
+                write!(f, "{}", if interactive { "interactive rebase" } else { "rebase" })?;
+                if let Some((current, total)) = step {
+                    write!(f, ", step {} of {}", current, total)?;
+                }
+            }
+            Operation::ApplyMailbox { step } => {
+                write!(f, "am")?;
+                if let Some((current, total)) = step {
+                    write!(f, ", patch {} of {}", current, total)?;
+                }
+            }
+            Operation::CherryPick { ref head, sequence } => {
+                write_sequence(f, "cherry-pick", head, sequence)?
+            }
+            Operation::Revert { ref head, sequence } => {
+                write_sequence(f, "revert", head, sequence)?
+            }
+            Operation::Bisect { good, bad } => write!(f, "bisect, {} good, {} bad", good, bad)?,
+        }
+        write!(f, " (`{}` to continue)", self.get_continue_command())
+    }
+}
+
+///
+/// Function to format a cherry-pick or a revert, with its commit, and if other commits are left.
+///
+fn write_sequence(f: &mut fmt::Formatter,
+                  name: &str,
+                  head: &Option<String>,
+                  sequence: bool)
+                  -> fmt::Result {
+    write!(f, "{}", name)?;
+    if let Some(ref head) = *head {
+        write!(f, " of {}", head)?;
+    }
+    if sequence {
+        write!(f, ", other commits left")?;
+    }
+    Ok(())
+}
+
+///
+/// Function to read the first line of a file of a git directory.
+///
+fn read_first_line(path: &Path) -> Option<String> {
+    let mut content = String::new();
+    match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+        Ok(_) => content.lines().next().map(|line| String::from(line.trim())),
+        Err(_) => None,
+    }
+}
+
+///
+/// Function to read the current step and the number of steps of a rebase, from two files that
+/// contain a number.
+///
+fn read_step(step_file: &Path, end_file: &Path) -> Option<(usize, usize)> {
+    let step = read_first_line(step_file).and_then(|step| step.parse::<usize>().ok());
+    let end = read_first_line(end_file).and_then(|end| end.parse::<usize>().ok());
+    match (step, end) {
+        (Some(step), Some(end)) => Some((step, end)),
+        _ => None,
+    }
+}
+
+///
+/// Function to get the short id of the commit written in a file of a git directory, like
+/// MERGE_HEAD.
+///
+fn read_head_short_id(repository: &Repository, head_file: &Path) -> Option<String> {
+    let head_id = match read_first_line(head_file).and_then(|head| Oid::from_str(&head).ok()) {
+        Some(head_id) => head_id,
+        None => return None,
+    };
+    let short_id = repository.find_object(head_id, None)
+        .ok()
+        .and_then(|object| object.short_id().ok())
+        .and_then(|short_id| short_id.as_str().map(String::from));
+    Some(short_id.unwrap_or_else(|| head_id.to_string()))
+}
+
+///
+/// Function to count the good and the bad commits marked during a bisection, from the names of
+/// their references.
+///
+/// The bisection references of a repository may be packed, so they are read through libgit2 -
+/// the ones of a linked working tree are only in its own git directory, where they are never
+/// packed.
+///
+fn count_bisect_refs(repository: &Repository, git_dir: &Path) -> (usize, usize) {
+    let names = if git_dir == repository.path() {
+        match repository.references_glob(&format!("{}/*", BISECT_REFS_DIR_NAME)) {
+            Ok(references) => {
+                references.filter_map(|reference| reference.ok())
+                    .filter_map(|reference| {
+                        reference.name()
+                            .and_then(|name| name.rsplit('/').next())
+                            .map(String::from)
+                    })
+                    .collect::<Vec<String>>()
+            }
+            Err(_) => return (0, 0),
+        }
+    } else {
+        match fs::read_dir(git_dir.join(BISECT_REFS_DIR_NAME)) {
+            Ok(entries) => {
+                entries.filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .collect::<Vec<String>>()
+            }
+            Err(_) => return (0, 0),
+        }
+    };
+    (names.iter().filter(|name| name.starts_with("good")).count(),
+     names.iter().filter(|name| name.starts_with("bad")).count())
+}
+
+///
+/// Function to get the state of a git repository from the files of its git directory, like
+/// libgit2 does - used for linked working trees, whose state is not the one of their main
+/// repository.
+///
+pub fn get_state_from_git_dir(git_dir: &Path) -> RepositoryState {
+    let rebase_merge_dir = git_dir.join(REBASE_MERGE_DIR_NAME);
+    let rebase_apply_dir = git_dir.join(REBASE_APPLY_DIR_NAME);
+    if rebase_merge_dir.join(REBASE_INTERACTIVE_FILE_NAME).is_file() {
+        return RepositoryState::RebaseInteractive;
+    }
+    if rebase_merge_dir.is_dir() {
+        return RepositoryState::RebaseMerge;
+    }
+    if rebase_apply_dir.join(REBASE_APPLY_REBASING_FILE_NAME).is_file() {
+        return RepositoryState::Rebase;
+    }
+    if rebase_apply_dir.join(REBASE_APPLY_APPLYING_FILE_NAME).is_file() {
+        return RepositoryState::ApplyMailbox;
+    }
+    if rebase_apply_dir.is_dir() {
+        return RepositoryState::ApplyMailboxOrRebase;
+    }
+    let has_sequence = git_dir.join(SEQUENCER_TODO_FILE_NAME).is_file();
+    if git_dir.join(MERGE_HEAD_FILE_NAME).is_file() {
+        return RepositoryState::Merge;
+    }
+    if git_dir.join(REVERT_HEAD_FILE_NAME).is_file() {
+        return if has_sequence {
+            RepositoryState::RevertSequence
+        } else {
+            RepositoryState::Revert
+        };
+    }
+    if git_dir.join(CHERRY_PICK_HEAD_FILE_NAME).is_file() {
+        return if has_sequence {
+            RepositoryState::CherryPickSequence
+        } else {
+            RepositoryState::CherryPick
+        };
+    }
+    if git_dir.join(BISECT_LOG_FILE_NAME).is_file() {
+        return RepositoryState::Bisect;
+    }
+    RepositoryState::Clean
+}
+
+///
+/// Function to get the operation in progress in a git repository, with its details read from its
+/// git directory.
+///
+/// This function returns None if there is no operation in progress.
+///
+pub fn get_operation(repository: &Repository,
+                     git_dir: &Path,
+                     state: RepositoryState)
+                     -> Option<Operation> {
+    let rebase_merge_dir = git_dir.join(REBASE_MERGE_DIR_NAME);
+    let rebase_apply_dir = git_dir.join(REBASE_APPLY_DIR_NAME);
+    let rebase_merge_step = || {
+        read_step(&rebase_merge_dir.join(REBASE_MERGE_STEP_FILE_NAME),
+                  &rebase_merge_dir.join(REBASE_MERGE_END_FILE_NAME))
+    };
+    let rebase_apply_step = || {
+        read_step(&rebase_apply_dir.join(REBASE_APPLY_STEP_FILE_NAME),
+                  &rebase_apply_dir.join(REBASE_APPLY_END_FILE_NAME))
+    };
+    match state {
+        RepositoryState::Clean => None,
+        RepositoryState::Merge => {
+            Some(Operation::Merge {
+                head: read_head_short_id(repository, &git_dir.join(MERGE_HEAD_FILE_NAME)),
+            })
+        }
+        RepositoryState::Revert |
+        RepositoryState::RevertSequence => {
+            Some(Operation::Revert {
+                head: read_head_short_id(repository, &git_dir.join(REVERT_HEAD_FILE_NAME)),
+                sequence: state == RepositoryState::RevertSequence,
+            })
+        }
+        RepositoryState::CherryPick |
+        RepositoryState::CherryPickSequence => {
+            Some(Operation::CherryPick {
+                head: read_head_short_id(repository, &git_dir.join(CHERRY_PICK_HEAD_FILE_NAME)),
+                sequence: state == RepositoryState::CherryPickSequence,
+            })
+        }
+        RepositoryState::Bisect => {
+            let (good, bad) = count_bisect_refs(repository, git_dir);
+            Some(Operation::Bisect {
+                good: good,
+                bad: bad,
+            })
+        }
+        RepositoryState::RebaseInteractive |
+        RepositoryState::RebaseMerge => {
+            Some(Operation::Rebase {
+                interactive: state == RepositoryState::RebaseInteractive,
+                step: rebase_merge_step(),
+            })
+        }
+        RepositoryState::Rebase |
+        RepositoryState::ApplyMailboxOrRebase => {
+            Some(Operation::Rebase {
+                interactive: false,
+                step: rebase_apply_step(),
+            })
+        }
+        RepositoryState::ApplyMailbox => {
+            Some(Operation::ApplyMailbox { step: rebase_apply_step() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Operation, get_operation, get_state_from_git_dir, read_step};
+    use git2::{Repository, RepositoryState, Signature};
+    use std::env;
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    fn get_git_dir(name: &str) -> PathBuf {
+        let git_dir = env::temp_dir()
+            .join(format!("gyro-operation-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&git_dir);
+        fs::create_dir_all(&git_dir).unwrap();
+        git_dir
+    }
+
+    fn write_file(path: &Path, content: &str) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+    }
+
+    fn get_state(name: &str, files: &[&str]) -> RepositoryState {
+        let git_dir = get_git_dir(name);
+        for file in files {
+            write_file(&git_dir.join(file), "");
+        }
+        let state = get_state_from_git_dir(&git_dir);
+        fs::remove_dir_all(&git_dir).unwrap();
+        state
+    }
+
+    #[test]
+    fn state_is_clean_without_operation_files() {
+        assert_eq!(get_state("clean", &[]), RepositoryState::Clean);
+    }
+
+    #[test]
+    fn state_of_rebase_merge_depends_on_interactive_file() {
+        assert_eq!(get_state("interactive", &["rebase-merge/interactive"]),
+                   RepositoryState::RebaseInteractive);
+        assert_eq!(get_state("merge-backend", &["rebase-merge/msgnum"]),
+                   RepositoryState::RebaseMerge);
+    }
+
+    #[test]
+    fn state_of_rebase_apply_depends_on_marker_file() {
+        assert_eq!(get_state("rebasing", &["rebase-apply/rebasing"]),
+                   RepositoryState::Rebase);
+        assert_eq!(get_state("applying", &["rebase-apply/applying"]),
+                   RepositoryState::ApplyMailbox);
+        assert_eq!(get_state("apply-unknown", &["rebase-apply/next"]),
+                   RepositoryState::ApplyMailboxOrRebase);
+    }
+
+    #[test]
+    fn rebase_takes_precedence_over_merge_head() {
+        assert_eq!(get_state("rebase-and-merge", &["rebase-apply/rebasing", "MERGE_HEAD"]),
+                   RepositoryState::Rebase);
+    }
+
+    #[test]
+    fn state_of_merge_revert_and_cherry_pick() {
+        assert_eq!(get_state("merge", &["MERGE_HEAD"]), RepositoryState::Merge);
+        assert_eq!(get_state("revert", &["REVERT_HEAD"]), RepositoryState::Revert);
+        assert_eq!(get_state("revert-sequence", &["REVERT_HEAD", "sequencer/todo"]),
+                   RepositoryState::RevertSequence);
+        assert_eq!(get_state("cherry-pick", &["CHERRY_PICK_HEAD"]),
+                   RepositoryState::CherryPick);
+        assert_eq!(get_state("cherry-pick-sequence", &["CHERRY_PICK_HEAD", "sequencer/todo"]),
+                   RepositoryState::CherryPickSequence);
+    }
+
+    #[test]
+    fn state_of_bisect() {
+        assert_eq!(get_state("bisect", &["BISECT_LOG"]), RepositoryState::Bisect);
+    }
+
+    #[test]
+    fn step_needs_both_numbers() {
+        let git_dir = get_git_dir("step");
+        write_file(&git_dir.join("next"), "2\n");
+        write_file(&git_dir.join("last"), "5\n");
+        write_file(&git_dir.join("empty"), "");
+        assert_eq!(read_step(&git_dir.join("next"), &git_dir.join("last")), Some((2, 5)));
+        assert_eq!(read_step(&git_dir.join("next"), &git_dir.join("empty")), None);
+        assert_eq!(read_step(&git_dir.join("next"), &git_dir.join("missing")), None);
+        fs::remove_dir_all(&git_dir).unwrap();
+    }
+
+    #[test]
+    fn operation_of_repository_is_read_from_its_git_dir() {
+        let path = get_git_dir("repository");
+        let repository = Repository::init(&path).unwrap();
+        let git_dir = repository.path().to_path_buf();
+        assert_eq!(get_operation(&repository, &git_dir, RepositoryState::Clean), None);
+
+        write_file(&git_dir.join("rebase-apply/next"), "1\n");
+        write_file(&git_dir.join("rebase-apply/last"), "3\n");
+        write_file(&git_dir.join("rebase-apply/applying"), "");
+        assert_eq!(get_operation(&repository, &git_dir, RepositoryState::ApplyMailbox),
+                   Some(Operation::ApplyMailbox { step: Some((1, 3)) }));
+        assert_eq!(get_operation(&repository, &git_dir, RepositoryState::Rebase),
+                   Some(Operation::Rebase {
+                       interactive: false,
+                       step: Some((1, 3)),
+                   }));
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn bisect_counts_loose_and_packed_refs() {
+        let path = get_git_dir("bisect-refs");
+        let repository = Repository::init(&path).unwrap();
+        let signature = Signature::now("gyro", "gyro@example.com").unwrap();
+        let tree_id = repository.index().unwrap().write_tree().unwrap();
+        let commit_id = {
+            let tree = repository.find_tree(tree_id).unwrap();
+            repository.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[])
+                .unwrap()
+        };
+        let git_dir = repository.path().to_path_buf();
+        write_file(&git_dir.join("packed-refs"),
+                   &format!("{} refs/bisect/good-{}\n", commit_id, commit_id));
+        repository.reference("refs/bisect/bad", commit_id, false, "bisect").unwrap();
+        assert_eq!(get_operation(&repository, &git_dir, RepositoryState::Bisect),
+                   Some(Operation::Bisect { good: 1, bad: 1 }));
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use git::RepositoryKind;
+use glob::{MatchOptions, Pattern};
+use scan::resolve_pattern;
+
+///
+/// Options used to match a path against a rule pattern: `*` never matches a path separator, but
+/// `**` matches any number of directories.
+///
+static PATH_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+///
+/// Options used to match a remote url against a rule pattern: `*` matches any sequence of
+/// characters, like `https://github.com/*`.
+///
+static REMOTE_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+///
+/// A rule to categorise new git repositories, as written in a `[[config.rules]]` section of the
+/// configuration file:
+/// `path`: The glob pattern of the path of matching git repositories (a leading `~` is the user
+/// home directory)
+/// `remote`: The glob pattern of the remote url of matching git repositories
+/// `kind`: The kind of matching git repositories (`normal`, `bare`, `worktree` or `submodule`)
+/// `category`: The category of matching git repositories - `watched`, `ignored`, or the name of a
+/// group
+///
+/// Each condition that is set must match.
+///
+#[derive(Clone, RustcDecodable)]
+pub struct CategoryRule {
+    pub path: Option<String>,
+    pub remote: Option<String>,
+    pub kind: Option<String>,
+    pub category: String,
+}
+
+///
+/// A rule to categorise new git repositories, with its patterns compiled:
+/// `path`: The pattern of the path of matching git repositories, if any
+/// `remote`: The pattern of the remote url of matching git repositories, if any
+/// `kind`: The kind of matching git repositories, if any
+/// `category`: The category of matching git repositories
+///
+#[derive(Clone)]
+pub struct CompiledRule {
+    path: Option<Pattern>,
+    remote: Option<Pattern>,
+    kind: Option<RepositoryKind>,
+    category: String,
+}
+
+impl CompiledRule {
+    ///
+    /// This function returns a Result type, that contains the compiled rule, or a message if one
+    /// of its patterns, or its kind, is not valid
+    ///
+    pub fn new(rule: &CategoryRule) -> Result<Self, String> {
+        let path = match rule.path {
+            Some(ref rule_path) => Some(compile_pattern(&resolve_pattern(rule_path))?),
+            None => None,
+        };
+        let remote = match rule.remote {
+            Some(ref rule_remote) => Some(compile_pattern(rule_remote)?),
+            None => None,
+        };
+        let kind = match rule.kind {
+            Some(ref rule_kind) => Some(rule_kind.parse::<RepositoryKind>()?),
+            None => None,
+        };
+        Ok(CompiledRule {
+            path: path,
+            remote: remote,
+            kind: kind,
+            category: rule.category.clone(),
+        })
+    }
+
+    ///
+    /// This method returns a boolean - true if the rule needs the remote url of git repositories,
+    /// else false
+    ///
+    pub fn needs_remote_url(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    ///
+    /// This method returns a boolean - true if the git repository, given by its path, kind and
+    /// remote url, matches each condition of the rule, else false
+    ///
+    pub fn matches(&self, path: &str, kind: RepositoryKind, remote_url: Option<&str>) -> bool {
+        if let Some(rule_kind) = self.kind {
+            if rule_kind != kind {
+                return false;
+            }
+        }
+        if let Some(ref rule_path) = self.path {
+            if !rule_path.matches_with(path, &PATH_MATCH_OPTIONS) {
+                return false;
+            }
+        }
+        if let Some(ref rule_remote) = self.remote {
+            match remote_url {
+                Some(remote_url) if rule_remote.matches_with(remote_url,
+                                                             &REMOTE_MATCH_OPTIONS) => (),
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+///
+/// Function to compile a rule pattern.
+///
+fn compile_pattern(pattern: &str) -> Result<Pattern, String> {
+    Pattern::new(pattern)
+        .map_err(|error| format!("Invalid rule pattern '{}': {}", pattern, error))
+}
+
+///
+/// Function to compile the rules of the configuration file, in order.
+///
+/// Invalid rules never match any git repository: this function also returns their messages, to
+/// be reported once.
+///
+pub fn compile_rules(rules: &[CategoryRule]) -> (Vec<CompiledRule>, Vec<String>) {
+    let mut compiled_rules = Vec::new();
+    let mut errors = Vec::new();
+    for rule in rules {
+        match CompiledRule::new(rule) {
+            Ok(compiled_rule) => compiled_rules.push(compiled_rule),
+            Err(error) => {
+                errors.push(format!("{} - the rule for {} is ignored", error, rule.category))
+            }
+        }
+    }
+    (compiled_rules, errors)
+}
+
+///
+/// Function to get the category of a new git repository, from the first rule it matches.
+///
+/// This function returns None if the git repository does not match any rule.
+///
+pub fn get_rule_category<'a>(rules: &'a [CompiledRule],
+                             path: &str,
+                             kind: RepositoryKind,
+                             remote_url: Option<&str>)
+                             -> Option<&'a str> {
+    rules.iter()
+        .find(|rule| rule.matches(path, kind, remote_url))
+        .map(|rule| rule.category.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile_rules, get_rule_category, CategoryRule};
+    use git::RepositoryKind;
+
+    fn get_rule(path: Option<&str>,
+                remote: Option<&str>,
+                kind: Option<&str>,
+                category: &str)
+                -> CategoryRule {
+        CategoryRule {
+            path: path.map(String::from),
+            remote: remote.map(String::from),
+            kind: kind.map(String::from),
+            category: String::from(category),
+        }
+    }
+
+    #[test]
+    fn path_rule_matches_one_directory_per_wildcard() {
+        let (rules, errors) = compile_rules(&[get_rule(Some("/gyro/work/*"), None, None, "job")]);
+        assert!(errors.is_empty());
+        assert_eq!(get_rule_category(&rules, "/gyro/work/api", RepositoryKind::Normal, None),
+                   Some("job"));
+        assert_eq!(get_rule_category(&rules, "/gyro/work/oss/api", RepositoryKind::Normal, None),
+                   None);
+        assert_eq!(get_rule_category(&rules, "/gyro/home/api", RepositoryKind::Normal, None),
+                   None);
+    }
+
+    #[test]
+    fn every_condition_of_a_rule_must_match() {
+        let (rules, _) = compile_rules(&[get_rule(Some("/gyro/**/*"),
+                                                  Some("https://github.com/*"),
+                                                  Some("bare"),
+                                                  "mirrors")]);
+        let remote_url = Some("https://github.com/gyro/api");
+        assert_eq!(get_rule_category(&rules, "/gyro/a/api", RepositoryKind::Bare, remote_url),
+                   Some("mirrors"));
+        assert_eq!(get_rule_category(&rules, "/gyro/a/api", RepositoryKind::Normal, remote_url),
+                   None);
+        assert_eq!(get_rule_category(&rules, "/gyro/a/api", RepositoryKind::Bare, None), None);
+        assert_eq!(get_rule_category(&rules,
+                                     "/gyro/a/api",
+                                     RepositoryKind::Bare,
+                                     Some("https://gitlab.com/gyro/api")),
+                   None);
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let (rules, _) = compile_rules(&[get_rule(Some("/gyro/work/tmp-*"), None, None, "ignored"),
+                                         get_rule(Some("/gyro/work/*"), None, None, "watched"),
+                                         get_rule(None, None, None, "others")]);
+        assert_eq!(get_rule_category(&rules, "/gyro/work/tmp-api", RepositoryKind::Normal, None),
+                   Some("ignored"));
+        assert_eq!(get_rule_category(&rules, "/gyro/work/api", RepositoryKind::Normal, None),
+                   Some("watched"));
+        assert_eq!(get_rule_category(&rules, "/gyro/api", RepositoryKind::Normal, None),
+                   Some("others"));
+    }
+
+    #[test]
+    fn invalid_rule_is_returned_as_an_error_and_never_matches() {
+        let (rules, errors) = compile_rules(&[get_rule(Some("/gyro/work["), None, None, "a"),
+                                              get_rule(None, None, Some("svn"), "b"),
+                                              get_rule(Some("/gyro/*"), None, None, "c")]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(get_rule_category(&rules, "/gyro/work[", RepositoryKind::Normal, None),
+                   Some("c"));
+    }
+}
@@ -1,9 +1,23 @@
-use GIT_DIR_NAME;
+use {GIT_CONFIG_FILE_NAME, GIT_DIR_NAME, GIT_HEAD_FILE_NAME, GIT_OBJECTS_DIR_NAME,
+     GIT_REFS_DIR_NAME, GITMODULES_FILE_NAME, IGNORE_FILE_NAME};
+use cache::{CachedDirectory, ScanCache};
+use exclude::ExcludeRules;
+use include::IncludeRules;
+use git::{get_gitdir_from_file, RepositoryKind, WORKTREE_COMMONDIR_FILE_NAME};
+use glob::{MatchOptions, Pattern};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs;
 use std::io::prelude::*;
 use std::io;
+use std::mem;
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
-use walkdir::{DirEntry, WalkDir};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 trait HiddenPath {
     ///
@@ -38,58 +52,1502 @@ impl HiddenPath for Path {
 
 trait GitTools {
     ///
-    /// Method to know if a given entry is a git repository
+    /// Method to know the kind of git repository of a given directory
     ///
-    fn is_git_repository(&self) -> bool;
+    fn get_git_repository_kind(&self) -> Option<RepositoryKind>;
 }
 
-impl GitTools for DirEntry {
+impl GitTools for Path {
     ///
-    /// This method returns a boolean - true if the DirEntry reference is a git repository, else
-    /// false
+    /// This method returns an Option type - the kind of git repository if the directory contains
+    /// a GIT_DIR_NAME directory or file, or if it is a git directory itself (bare repository),
+    /// else None
     ///
-    fn is_git_repository(&self) -> bool {
-        if fs::metadata(self.path()).unwrap().is_dir() {
-            // If the DirEntry is a directory, it returns if the DirEntry metadata is equals to GIT_DIR_NAME
-            return self.file_name().to_str().unwrap() == GIT_DIR_NAME;
+    fn get_git_repository_kind(&self) -> Option<RepositoryKind> {
+        let git_dir = self.join(GIT_DIR_NAME);
+        match fs::metadata(&git_dir) {
+            Ok(ref metadata) if metadata.is_dir() => return Some(RepositoryKind::Normal),
+            Ok(ref metadata) if metadata.is_file() => return get_gitdir_file_kind(&git_dir),
+            _ => (),
+        }
+        if self.join(GIT_HEAD_FILE_NAME).is_file() && self.join(GIT_OBJECTS_DIR_NAME).is_dir() &&
+           self.join(GIT_REFS_DIR_NAME).is_dir() {
+            return Some(RepositoryKind::Bare);
+        }
+        None
+    }
+}
+
+///
+/// Function to get the kind of git repository of a directory.
+///
+/// This function returns None if the directory is not a git repository.
+///
+pub fn get_repository_kind(directory: &Path) -> Option<RepositoryKind> {
+    directory.get_git_repository_kind()
+}
+
+///
+/// Function to get the kind of git repository that uses a GIT_DIR_NAME file.
+///
+/// This file contains the path of the git directory: the git directory of a linked working tree
+/// contains a `commondir` file, with the path of the main repository, and the git directory of a
+/// submodule, in its parent repository, sets the `core.worktree` option to the submodule checkout.
+/// Other ones are git directories separated from their working directory.
+///
+fn get_gitdir_file_kind(gitdir_file: &Path) -> Option<RepositoryKind> {
+    let git_dir = match get_gitdir_from_file(gitdir_file) {
+        Some(git_dir) => git_dir,
+        None => return None,
+    };
+    if git_dir.join(WORKTREE_COMMONDIR_FILE_NAME).is_file() {
+        return Some(RepositoryKind::Worktree);
+    }
+    if has_core_worktree(&git_dir) {
+        return Some(RepositoryKind::Submodule);
+    }
+    Some(RepositoryKind::Normal)
+}
+
+///
+/// Function to know if the configuration of a git directory sets the `core.worktree` option.
+///
+fn has_core_worktree(git_dir: &Path) -> bool {
+    let mut content = String::new();
+    match fs::File::open(git_dir.join(GIT_CONFIG_FILE_NAME)) {
+        Ok(mut file) => {
+            if file.read_to_string(&mut content).is_err() {
+                return false;
+            }
+        }
+        Err(_) => return false,
+    }
+    let mut in_core_section = false;
+    for line in content.lines().map(|line| line.trim()) {
+        if line.starts_with('[') {
+            in_core_section = line.to_lowercase() == "[core]";
+        } else if in_core_section &&
+                  line.splitn(2, '=').next().map(|key| key.trim().to_lowercase()) ==
+                  Some(String::from("worktree")) {
+            return true;
+        }
+    }
+    false
+}
+
+///
+/// A git repository found during a scan:
+/// `path`: The local path of the git repository
+/// `parent`: The local path of the git repository that contains this one, if it is a nested
+/// repository
+/// `kind`: The kind of the git repository
+/// `aliases`: The other local paths of the same git repository, through symbolic links
+///
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct FoundRepository {
+    pub path: String,
+    pub parent: Option<String>,
+    pub kind: RepositoryKind,
+    pub aliases: Vec<String>,
+}
+
+///
+/// The reason why a directory has not been walked into during a scan:
+/// `Excluded` is corresponding to a directory that matches an exclusion pattern
+/// `MountPoint` is corresponding to another file system, or to a mount point to never walk into
+/// `Submodule` is corresponding to a registered submodule of a git repository
+/// `SymlinkLoop` is corresponding to a symbolic link to one of its parent directories
+///
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SkipReason {
+    Excluded,
+    MountPoint,
+    Submodule,
+    SymlinkLoop,
+}
+
+///
+/// A directory that has not been walked into during a scan:
+/// `path`: The local path of the directory
+/// `reason`: Why the directory has not been walked into
+///
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct SkippedDirectory {
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+///
+/// The kind of an error encountered during a scan:
+/// `BrokenLink` is corresponding to a symbolic link whose target does not exist
+/// `PermissionDenied` is corresponding to a directory or a link that cannot be read by the user
+/// `Io` is corresponding to any other I/O error
+/// `Internal` is corresponding to a directory whose scan stopped unexpectedly (a thread of the
+/// scan panicked)
+/// `InvalidPattern` is corresponding to an include pattern, or a pattern of a .gyroignore file,
+/// that is not a valid glob
+///
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum ScanErrorKind {
+    BrokenLink,
+    PermissionDenied,
+    Io,
+    Internal,
+    InvalidPattern,
+}
+
+impl ScanErrorKind {
+    ///
+    /// This method returns the description of the kind of error, as printed in reports
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ScanErrorKind::BrokenLink => "broken link",
+            ScanErrorKind::PermissionDenied => "permission denied",
+            ScanErrorKind::Io => "I/O error",
+            ScanErrorKind::Internal => "internal error",
+            ScanErrorKind::InvalidPattern => "invalid pattern",
+        }
+    }
+}
+
+impl fmt::Display for ScanErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+///
+/// An error encountered during a scan:
+/// `path`: The local path that cannot be read - or the scan root, or the .gyroignore file, of an
+/// invalid pattern
+/// `kind`: The kind of error
+/// `message`: The description of the error
+///
+#[derive(Clone, Debug)]
+pub struct ScanError {
+    pub path: String,
+    pub kind: ScanErrorKind,
+    pub message: String,
+}
+
+impl ScanError {
+    ///
+    /// The function to instanciate a new ScanError structure, from an I/O error on a path
+    ///
+    pub fn new(path: &Path, error: &io::Error) -> Self {
+        ScanError {
+            path: path.to_string_lossy().into_owned(),
+            kind: match error.kind() {
+                io::ErrorKind::PermissionDenied => ScanErrorKind::PermissionDenied,
+                _ => ScanErrorKind::Io,
+            },
+            message: error.to_string(),
+        }
+    }
+
+    ///
+    /// The function to instanciate a new ScanError structure, for a symbolic link whose target
+    /// does not exist
+    ///
+    fn broken_link(path: &Path) -> Self {
+        let target = fs::read_link(path)
+            .map(|target| target.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        ScanError {
+            path: path.to_string_lossy().into_owned(),
+            kind: ScanErrorKind::BrokenLink,
+            message: format!("The target {:?} does not exist", target),
+        }
+    }
+
+    ///
+    /// The function to instanciate a new ScanError structure, for a directory whose scan stopped
+    /// unexpectedly
+    ///
+    fn internal(path: &Path, message: &str) -> Self {
+        ScanError {
+            path: path.to_string_lossy().into_owned(),
+            kind: ScanErrorKind::Internal,
+            message: String::from(message),
+        }
+    }
+
+    ///
+    /// The function to instanciate a new ScanError structure, for an invalid pattern given for a
+    /// scan root, or written in a .gyroignore file
+    ///
+    fn invalid_pattern(path: &Path, message: &str) -> Self {
+        ScanError {
+            path: path.to_string_lossy().into_owned(),
+            kind: ScanErrorKind::InvalidPattern,
+            message: String::from(message),
+        }
+    }
+}
+
+///
+/// The report of a scan:
+/// `repositories`: The git repositories found, sorted by path
+/// `skipped`: The directories that have not been walked into, sorted by path
+/// `errors`: The errors encountered, like directories that cannot be read
+/// `positions`: The positions of the directories walked into, if they have been requested with
+/// `Scanner::keep_positions`, sorted by path
+/// `elapsed`: The duration of the scan
+///
+pub struct ScanReport {
+    pub repositories: Vec<FoundRepository>,
+    pub skipped: Vec<SkippedDirectory>,
+    pub errors: Vec<ScanError>,
+    pub positions: Vec<WalkPosition>,
+    pub elapsed: Duration,
+}
+
+impl ScanReport {
+    ///
+    /// This method returns the number of errors of the given kind
+    ///
+    pub fn count_errors(&self, kind: ScanErrorKind) -> usize {
+        self.errors.iter().filter(|error| error.kind == kind).count()
+    }
+}
+
+///
+/// A trait to follow the progress of a scan.
+/// Methods may be called from several threads, and do nothing by default.
+///
+pub trait ScanProgress: Send + Sync {
+    ///
+    /// Method called when the scan of a root directory starts
+    ///
+    fn on_root_started(&self, _root: &Path) {}
+
+    ///
+    /// Method called when a git repository is found
+    ///
+    fn on_repository_found(&self, _repository: &FoundRepository) {}
+
+    ///
+    /// Method called when the scan of a root directory is done
+    ///
+    fn on_root_finished(&self, _root: &Path) {}
+}
+
+///
+/// Options of a scan:
+/// `exclude_rules`: The patterns of directories to never walk into
+/// `nested`: If true, the content of a git repository is walked into, to find nested git
+/// repositories that are not registered submodules - else, the scan stops at the first git
+/// repository found
+/// `jobs`: The number of threads that walk into directories
+/// `one_file_system`: If true, directories on another file system than their parent directory are
+/// not walked into
+/// `skipped_mounts`: The canonical paths of the mount points to never walk into
+/// `skipped_mount_identities`: The identities of the mount points to never walk into, to find
+/// them through any path
+/// `min_depth`: The minimal depth of git repositories, from the scan root
+/// `max_depth`: The maximal depth of git repositories, from the scan root, if any
+/// `include_patterns`: The patterns of git repositories to find - every git repository if empty
+/// `include_rules`: The include patterns, resolved against the current scan root
+/// `keep_positions`: If true, the position of each directory walked into is kept in the report
+/// `progress`: The object to notify of the progress of the scan
+///
+#[derive(Clone)]
+struct ScanOptions {
+    exclude_rules: ExcludeRules,
+    nested: bool,
+    jobs: usize,
+    one_file_system: bool,
+    skipped_mounts: Vec<PathBuf>,
+    skipped_mount_identities: Vec<(u64, u64)>,
+    min_depth: usize,
+    max_depth: Option<usize>,
+    include_patterns: Vec<String>,
+    include_rules: IncludeRules,
+    keep_positions: bool,
+    progress: Option<Arc<ScanProgress>>,
+}
+
+///
+/// A builder to scan directories, in order to find git repositories:
+/// `roots`: The directories to scan
+/// `options`: The options of the scan
+/// `cache`: The scan cache, updated by each scan - a directory that has not been modified since
+/// it has been cached is not read again
+///
+pub struct Scanner {
+    roots: Vec<PathBuf>,
+    options: ScanOptions,
+    cache: ScanCache,
+}
+
+impl Scanner {
+    ///
+    /// The function to instanciate a new Scanner structure, without any root directory, that stops
+    /// at the first git repository found, using a single thread and an empty cache
+    ///
+    pub fn new() -> Self {
+        Scanner {
+            roots: Vec::new(),
+            options: ScanOptions {
+                exclude_rules: ExcludeRules::new(&[]),
+                nested: false,
+                jobs: 1,
+                one_file_system: false,
+                skipped_mounts: Vec::new(),
+                skipped_mount_identities: Vec::new(),
+                min_depth: 0,
+                max_depth: None,
+                include_patterns: Vec::new(),
+                include_rules: IncludeRules::new(&[], Path::new("/")),
+                keep_positions: false,
+                progress: None,
+            },
+            cache: ScanCache::new(),
+        }
+    }
+
+    ///
+    /// A method to add a directory to scan - a directory added twice is scanned once
+    ///
+    pub fn root(mut self, root: PathBuf) -> Self {
+        if !self.roots.contains(&root) {
+            self.roots.push(root);
+        }
+        self
+    }
+
+    ///
+    /// A method to set the patterns of directories to never walk into
+    ///
+    pub fn exclude(mut self, exclude_rules: ExcludeRules) -> Self {
+        self.options.exclude_rules = exclude_rules;
+        self
+    }
+
+    ///
+    /// A method to walk into git repositories, to find nested git repositories
+    ///
+    pub fn nested(mut self, nested: bool) -> Self {
+        self.options.nested = nested;
+        self
+    }
+
+    ///
+    /// A method to set the number of threads that walk into directories (at least one)
+    ///
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.options.jobs = if jobs > 0 { jobs } else { 1 };
+        self
+    }
+
+    ///
+    /// A method to never walk into a directory on another file system than its parent directory
+    ///
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.options.one_file_system = one_file_system;
+        self
+    }
+
+    ///
+    /// A method to add mount points to never walk into, like the ones of
+    /// `mount::get_mount_points_of_types` - they are skipped even if they are found through a
+    /// symbolic link
+    ///
+    pub fn skip_mounts(mut self, mount_points: Vec<PathBuf>) -> Self {
+        self.options.skipped_mounts.extend(mount_points.into_iter()
+            .map(|mount_point| fs::canonicalize(&mount_point).unwrap_or(mount_point)));
+        self
+    }
+
+    ///
+    /// A method to ignore git repositories that are less deep than `min_depth` directories, from
+    /// the scan root
+    ///
+    pub fn min_depth(mut self, min_depth: usize) -> Self {
+        self.options.min_depth = min_depth;
+        self
+    }
+
+    ///
+    /// A method to never walk into directories deeper than `max_depth` directories, from the scan
+    /// root
+    ///
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.options.max_depth = Some(max_depth);
+        self
+    }
+
+    ///
+    /// A method to only find git repositories that match one of the given glob patterns, like
+    /// `~/code/*/*` - relative patterns are relative to each scan root
+    ///
+    pub fn include(mut self, patterns: &[String]) -> Self {
+        self.options.include_patterns.extend(patterns.iter()
+            .map(|pattern| expand_home_dir(pattern).to_string_lossy().into_owned()));
+        self
+    }
+
+    ///
+    /// A method to set the scan cache, from a previous scan
+    ///
+    pub fn cache(mut self, cache: ScanCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    ///
+    /// A method to keep the position of each directory walked into in the report of the scan, to
+    /// walk into them again later with the options of the scan, like `watch::RepositoryWatcher`
+    /// does
+    ///
+    pub fn keep_positions(mut self, keep_positions: bool) -> Self {
+        self.options.keep_positions = keep_positions;
+        self
+    }
+
+    ///
+    /// A method to set the object to notify of the progress of the scan
+    ///
+    pub fn progress(mut self, progress: Arc<ScanProgress>) -> Self {
+        self.options.progress = Some(progress);
+        self
+    }
+
+    ///
+    /// A method to get the scan cache, updated by the previous scans
+    ///
+    pub fn get_cache(&self) -> &ScanCache {
+        &self.cache
+    }
+
+    ///
+    /// A method to get the scan cache, updated by the previous scans, in order to update it
+    ///
+    pub fn get_cache_mut(&mut self) -> &mut ScanCache {
+        &mut self.cache
+    }
+
+    ///
+    /// This method returns the options of the scan of a root directory: include patterns are
+    /// resolved against it, and mount points to never walk into are identified
+    ///
+    fn get_root_options(&self, root: &Path) -> ScanOptions {
+        let mut root_options = self.options.clone();
+        root_options.include_rules = IncludeRules::new(&self.options.include_patterns, root);
+        root_options.skipped_mount_identities = self.options
+            .skipped_mounts
+            .iter()
+            .filter_map(|mount_point| fs::metadata(mount_point).ok())
+            .filter_map(|metadata| get_file_identity(&metadata))
+            .collect();
+        root_options
+    }
+
+    ///
+    /// This method scans each root directory, and returns a ScanReport type.
+    ///
+    /// Results of each root directory are merged, sorted and deduplicated, so they do not depend
+    /// on the number of threads.
+    /// A git repository found through several paths is returned once, with the other paths as
+    /// aliases.
+    ///
+    pub fn scan(&mut self) -> ScanReport {
+        let started = Instant::now();
+        let mut report = ScanReport {
+            repositories: Vec::new(),
+            skipped: Vec::new(),
+            errors: Vec::new(),
+            positions: Vec::new(),
+            elapsed: Duration::from_secs(0),
+        };
+        for root in &self.roots {
+            if let Some(ref progress) = self.options.progress {
+                progress.on_root_started(root);
+            }
+            let root_options = self.get_root_options(root);
+            for error in root_options.include_rules.get_errors() {
+                report.errors.push(ScanError::invalid_pattern(root, error));
+            }
+            scan_root(root, &root_options, &mut self.cache, &mut report);
+            if let Some(ref progress) = self.options.progress {
+                progress.on_root_finished(root);
+            }
+        }
+        report.repositories.sort();
+        report.repositories.dedup();
+        merge_aliases(&mut report.repositories);
+        report.repositories.sort();
+        report.skipped.sort();
+        report.skipped.dedup();
+        report.errors.sort_by(|first, second| first.path.cmp(&second.path));
+        report.positions.sort_by(|first, second| first.get_path().cmp(second.get_path()));
+        report.elapsed = started.elapsed();
+        report
+    }
+}
+
+///
+/// A git repository that contains the directories to walk into:
+/// `path`: The local path of the git repository
+/// `submodules`: The local paths of its registered submodules
+///
+struct ParentRepository {
+    path: PathBuf,
+    submodules: Vec<PathBuf>,
+}
+
+///
+/// A directory that has to be walked into:
+/// `path`: The local path of the directory
+/// `parent`: The nearest git repository that contains the directory, if nested git repositories
+/// are requested
+/// `ignore_files`: The patterns of .gyroignore files found between the scan root and the
+/// directory, with the directory that contains each of them
+/// `ancestors`: The identity of the parent directory, with the identities of its own parents
+/// `depth`: The number of directories between the scan root and the directory
+///
+#[derive(Clone)]
+struct PendingDirectory {
+    path: PathBuf,
+    parent: Option<Arc<ParentRepository>>,
+    ignore_files: Vec<Arc<(PathBuf, ExcludeRules)>>,
+    ancestors: Option<Arc<DirectoryIdentity>>,
+    depth: usize,
+}
+
+///
+/// The identity of a directory walked into, on the file system:
+/// `device`: The device that contains the directory
+/// `inode`: The inode of the directory
+/// `parent`: The identity of its parent directory, as walked into
+///
+struct DirectoryIdentity {
+    device: u64,
+    inode: u64,
+    parent: Option<Arc<DirectoryIdentity>>,
+}
+
+impl DirectoryIdentity {
+    ///
+    /// This method returns a boolean - true if the given identity is the one of this directory or
+    /// of one of its parents, else false
+    ///
+    fn contains(&self, identity: (u64, u64)) -> bool {
+        let mut current = Some(self);
+        while let Some(directory) = current {
+            if (directory.device, directory.inode) == identity {
+                return true;
+            }
+            current = directory.parent.as_ref().map(|parent| &**parent);
+        }
+        false
+    }
+}
+
+///
+/// The state shared by the threads of a scan:
+/// `pending`: The directories that remain to walk into
+/// `active`: The number of threads that are walking into a directory
+/// `found`: The git repositories found
+/// `skipped`: The directories that have not been walked into
+/// `errors`: The errors encountered
+/// `cached`: The directories walked into, to cache
+/// `positions`: The positions of the directories walked into, if they are kept
+///
+struct WalkState {
+    pending: Vec<PendingDirectory>,
+    active: usize,
+    found: Vec<FoundRepository>,
+    skipped: Vec<SkippedDirectory>,
+    errors: Vec<ScanError>,
+    cached: Vec<(String, CachedDirectory)>,
+    positions: Vec<WalkPosition>,
+}
+
+///
+/// The result of walking into a single directory:
+/// `found`: The git repository found in this directory, if any
+/// `subdirectories`: The directories that remain to walk into
+/// `skipped`: The subdirectories that will not be walked into
+/// `errors`: The errors encountered
+/// `cached`: The content of the directory to cache, if it has been read
+///
+struct WalkedDirectory {
+    found: Option<FoundRepository>,
+    subdirectories: Vec<PendingDirectory>,
+    skipped: Vec<SkippedDirectory>,
+    errors: Vec<ScanError>,
+    cached: Option<CachedDirectory>,
+}
+
+///
+/// A directory to walk into with the options of a scan, along with what it inherits from its
+/// parent directories (.gyroignore files, depth, file system...):
+/// `directory`: The directory to walk into
+/// `root`: The scan root that contains the directory
+/// `options`: The options of the scan of this root
+///
+/// Walking into directories one by one, from the positions kept by a scan
+/// (`Scanner::keep_positions`), finds the same git repositories as the scan.
+///
+#[derive(Clone)]
+pub struct WalkPosition {
+    directory: PendingDirectory,
+    root: PathBuf,
+    options: Arc<ScanOptions>,
+}
+
+///
+/// The result of walking into a single directory, from its position:
+/// `found`: The git repository found in this directory, if any
+/// `subdirectories`: The positions of the subdirectories to walk into
+/// `is_skipped`: True if the directory itself must not be walked into, like a mount point
+/// `errors`: The errors encountered
+///
+pub struct WalkStep {
+    pub found: Option<FoundRepository>,
+    pub subdirectories: Vec<WalkPosition>,
+    pub is_skipped: bool,
+    pub errors: Vec<ScanError>,
+}
+
+impl WalkPosition {
+    ///
+    /// This method returns the local path of the directory
+    ///
+    pub fn get_path(&self) -> &Path {
+        &self.directory.path
+    }
+
+    ///
+    /// This method returns a boolean - true if the directory is a scan root, else false
+    ///
+    pub fn is_root(&self) -> bool {
+        self.directory.depth == 0
+    }
+
+    ///
+    /// This method walks into the directory, without any scan cache, and returns a WalkStep type
+    ///
+    pub fn walk(&self) -> WalkStep {
+        let walked_directory = walk_directory(self.directory.clone(),
+                                              &self.root,
+                                              &self.options,
+                                              &ScanCache::new());
+        let path = self.directory.path.to_string_lossy();
+        WalkStep {
+            found: walked_directory.found,
+            subdirectories: walked_directory.subdirectories
+                .into_iter()
+                .map(|directory| {
+                    WalkPosition {
+                        directory: directory,
+                        root: self.root.clone(),
+                        options: self.options.clone(),
+                    }
+                })
+                .collect(),
+            is_skipped: walked_directory.skipped.iter().any(|skipped| skipped.path == path),
+            errors: walked_directory.errors,
+        }
+    }
+}
+
+///
+/// Function to get the paths of submodules registered by a git repository.
+///
+/// This function returns a vector of PathBuf, which each one is the local path of a submodule,
+/// as written in the .gitmodules file of the repository.
+///
+fn get_submodule_paths(repository: &Path) -> Vec<PathBuf> {
+    let mut content = String::new();
+    match fs::File::open(repository.join(GITMODULES_FILE_NAME)) {
+        Ok(mut file) => {
+            if file.read_to_string(&mut content).is_err() {
+                return Vec::new();
+            }
+        }
+        Err(_) => return Vec::new(),
+    }
+    content.lines()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with("path"))
+        .filter_map(|line| line.splitn(2, '=').nth(1))
+        .map(|submodule_path| repository.join(submodule_path.trim()))
+        .collect()
+}
+
+///
+/// Function to get the path of a scan root, as written by the user.
+///
+/// This function returns a PathBuf type, where a leading `~` has been replaced by the user home
+/// directory.
+///
+pub fn expand_home_dir(root: &str) -> PathBuf {
+    if root == "~" || root.starts_with("~/") {
+        if let Some(home_dir) = env::home_dir() {
+            if root.len() > 2 {
+                return home_dir.join(&root[2..]);
+            }
+            return home_dir;
+        }
+    }
+    PathBuf::from(root)
+}
+
+///
+/// Function to get the absolute path of a scan root, as written by the user - a leading `~` is
+/// replaced by the user home directory, and the path is canonicalized.
+///
+/// This function returns an io::Error if the scan root cannot be resolved, like a directory that
+/// does not exist.
+///
+pub fn resolve_root(root: &str) -> io::Result<PathBuf> {
+    fs::canonicalize(expand_home_dir(root))
+}
+
+///
+/// Function to get the canonical path of a local path, to compare it with the paths found by a
+/// scan, whose roots are canonicalized.
+///
+/// A path that does not exist (anymore) is resolved from its parent directory, if it exists, else
+/// it is returned as it is.
+///
+pub fn canonicalize_path(path: &Path) -> PathBuf {
+    if let Ok(canonical_path) = fs::canonicalize(path) {
+        return canonical_path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            fs::canonicalize(parent)
+                .map(|parent| parent.join(name))
+                .unwrap_or_else(|_| path.to_path_buf())
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+///
+/// Function to get a glob pattern of paths, as written by the user, that can be matched against
+/// the paths found by a scan - a leading `~` is replaced by the user home directory, and the
+/// directories before the first wildcard are canonicalized, like scan roots.
+///
+pub fn resolve_pattern(pattern: &str) -> String {
+    let pattern = expand_home_dir(pattern);
+    if !pattern.is_absolute() {
+        return pattern.to_string_lossy().into_owned();
+    }
+    let mut literal_prefix = PathBuf::new();
+    let mut rest = PathBuf::new();
+    for component in pattern.components() {
+        let name = component.as_os_str().to_string_lossy();
+        let has_wildcard = name.contains(|character| "*?[".contains(character));
+        if rest.as_os_str().is_empty() && !has_wildcard {
+            literal_prefix.push(component.as_os_str());
+        } else {
+            rest.push(component.as_os_str());
+        }
+    }
+    match fs::canonicalize(&literal_prefix) {
+        Ok(canonical_prefix) => canonical_prefix.join(rest).to_string_lossy().into_owned(),
+        Err(_) => pattern.to_string_lossy().into_owned(),
+    }
+}
+
+///
+/// Function to know if a directory, found during a scan, must not be walked into.
+///
+/// Exclusion patterns from the configuration file are relative to the scan root, and patterns of
+/// a .gyroignore file are relative to the directory that contains it.
+/// The deepest .gyroignore file has the last word.
+///
+fn is_excluded(path: &Path,
+               root: &Path,
+               options: &ScanOptions,
+               ignore_files: &Vec<Arc<(PathBuf, ExcludeRules)>>)
+               -> bool {
+    // Never walk into the content of git directories
+    if path.file_name().map_or(false, |name| name == GIT_DIR_NAME) {
+        return true;
+    }
+    let mut excluded = options.exclude_rules.is_excluded(path.strip_prefix(root).unwrap_or(path));
+    for ignore_file in ignore_files {
+        let (ref directory, ref rules) = **ignore_file;
+        if let Some(is_excluded) = rules.is_excluded(path.strip_prefix(directory).unwrap()) {
+            excluded = Some(is_excluded);
+        }
+    }
+    excluded == Some(true)
+}
+
+///
+/// Function to get the identity of a file on the file system: its device and its inode.
+///
+#[cfg(unix)]
+fn get_file_identity(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+///
+/// Function to get the identity of a file on the file system, which is not available on this
+/// platform.
+///
+#[cfg(not(unix))]
+fn get_file_identity(_: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+///
+/// Function to know if a symbolic link to a directory points to the directory that contains it,
+/// or to one of its parent directories, which would make the scan loop forever.
+///
+fn is_symlink_loop(path: &Path, ancestors: &Option<Arc<DirectoryIdentity>>) -> bool {
+    let identity = fs::metadata(path).ok().and_then(|metadata| get_file_identity(&metadata));
+    match (identity, ancestors) {
+        (Some(identity), &Some(ref ancestors)) => ancestors.contains(identity),
+        // Without identities, compare the canonical paths
+        _ => {
+            match (fs::canonicalize(path), path.parent().map(fs::canonicalize)) {
+                (Ok(target), Some(Ok(parent))) => parent.starts_with(target),
+                _ => true,
+            }
+        }
+    }
+}
+
+///
+/// Function to merge git repositories found through several paths, because of symbolic links.
+///
+/// Git repositories are the same if they have the same identity on the file system, or the same
+/// canonical path.
+/// The primary path of a git repository is its canonical path if it has been found through it, or
+/// the shortest path found - other paths are its aliases.
+///
+fn merge_aliases(git_path: &mut Vec<FoundRepository>) {
+    let mut merged_repositories: Vec<FoundRepository> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for repository in git_path.drain(..) {
+        let canonical_path = fs::canonicalize(&repository.path)
+            .map(|canonical_path| canonical_path.to_string_lossy().into_owned())
+            .unwrap_or(repository.path.clone());
+        let identity = fs::metadata(&repository.path)
+            .ok()
+            .and_then(|metadata| get_file_identity(&metadata))
+            .map(|(device, inode)| format!("{}:{}", device, inode))
+            .unwrap_or(canonical_path.clone());
+        let merged_repository = match positions.get(&identity) {
+            Some(&position) => &mut merged_repositories[position],
+            None => {
+                positions.insert(identity, merged_repositories.len());
+                merged_repositories.push(repository);
+                continue;
+            }
+        };
+        let is_primary = repository.path == canonical_path ||
+                         (merged_repository.path != canonical_path &&
+                          (repository.path.len(), &repository.path) <
+                          (merged_repository.path.len(), &merged_repository.path));
+        if is_primary {
+            let previous_repository = mem::replace(merged_repository, repository);
+            merged_repository.aliases = previous_repository.aliases;
+            merged_repository.aliases.push(previous_repository.path);
+        } else {
+            merged_repository.aliases.push(repository.path);
+        }
+        merged_repository.aliases.sort();
+    }
+    git_path.append(&mut merged_repositories);
+}
+
+///
+/// Function to list the content of a directory, to cache it.
+///
+fn list_directory(path: &Path,
+                  cached_directory: &mut CachedDirectory,
+                  errors: &mut Vec<ScanError>)
+                  -> io::Result<()> {
+    let mut subdirectories = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let (file_name, file_type) = match entry.and_then(|entry| {
+            entry.file_type().map(|file_type| (entry.file_name(), file_type))
+        }) {
+            Ok((file_name, file_type)) => (file_name.to_string_lossy().into_owned(), file_type),
+            Err(error) => {
+                errors.push(ScanError::new(path, &error));
+                continue;
+            }
+        };
+        if file_type.is_dir() {
+            subdirectories.push(file_name);
+        } else if file_type.is_symlink() {
+            cached_directory.links.push(file_name);
+        } else if file_name == IGNORE_FILE_NAME {
+            cached_directory.ignore_file = true;
+        }
+    }
+    cached_directory.subdirectories = Some(subdirectories);
+    Ok(())
+}
+
+///
+/// Function to walk into a single directory.
+///
+/// A directory that has not been modified since the previous scan is not read again: its content
+/// is taken from the scan cache.
+///
+fn walk_directory(directory: PendingDirectory,
+                  root: &Path,
+                  options: &ScanOptions,
+                  cache: &ScanCache)
+                  -> WalkedDirectory {
+    let PendingDirectory { path, mut parent, mut ignore_files, ancestors, depth } = directory;
+    let mut walked_directory = WalkedDirectory {
+        found: None,
+        subdirectories: Vec::new(),
+        skipped: Vec::new(),
+        errors: Vec::new(),
+        cached: None,
+    };
+    let metadata = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            walked_directory.errors.push(ScanError::new(&path, &error));
+            return walked_directory;
+        }
+    };
+    let identity = match get_file_identity(&metadata) {
+        // Another file system is mounted on this directory
+        Some((device, _)) if options.one_file_system &&
+                             ancestors.as_ref().map_or(false, |parent| parent.device != device) => {
+            walked_directory.skipped.push(SkippedDirectory {
+                path: path.to_string_lossy().into_owned(),
+                reason: SkipReason::MountPoint,
+            });
+            return walked_directory;
+        }
+        // A mount point to never walk into, found through another path than its own
+        Some(identity) if ancestors.is_some() &&
+                          options.skipped_mount_identities.contains(&identity) => {
+            walked_directory.skipped.push(SkippedDirectory {
+                path: path.to_string_lossy().into_owned(),
+                reason: SkipReason::MountPoint,
+            });
+            return walked_directory;
+        }
+        Some((device, inode)) => {
+            Some(Arc::new(DirectoryIdentity {
+                device: device,
+                inode: inode,
+                parent: ancestors,
+            }))
+        }
+        None => None,
+    };
+    let mut cached_directory = match cache.directories.get(&*path.to_string_lossy()) {
+        Some(cached_directory) if cached_directory.is_up_to_date(&metadata) => {
+            cached_directory.clone()
+        }
+        _ => {
+            let mut cached_directory = CachedDirectory::new(&metadata);
+            cached_directory.kind = path.get_git_repository_kind().map(|kind| kind.to_string());
+            cached_directory
+        }
+    };
+    let kind = cached_directory.kind
+        .as_ref()
+        .and_then(|kind| kind.parse::<RepositoryKind>().ok());
+    if let Some(kind) = kind {
+        if let Some(ref parent_repository) = parent {
+            // Registered submodules are part of their parent repository
+            if parent_repository.submodules.contains(&path) {
+                walked_directory.skipped.push(SkippedDirectory {
+                    path: path.to_string_lossy().into_owned(),
+                    reason: SkipReason::Submodule,
+                });
+                walked_directory.cached = Some(cached_directory);
+                return walked_directory;
+            }
+        }
+        // Git repositories out of the requested depths or patterns are walked into like other
+        // directories
+        if depth >= options.min_depth && options.include_rules.matches(&path) {
+            walked_directory.found = Some(FoundRepository {
+                path: path.to_string_lossy().into_owned(),
+                parent: parent.as_ref()
+                    .map(|parent_repository| {
+                        parent_repository.path.to_string_lossy().into_owned()
+                    }),
+                kind: kind,
+                aliases: Vec::new(),
+            });
+            // Do not walk into the git repository - the content of a bare repository is its git
+            // directory
+            if !options.nested || kind == RepositoryKind::Bare {
+                walked_directory.cached = Some(cached_directory);
+                return walked_directory;
+            }
+            parent = Some(Arc::new(ParentRepository {
+                submodules: get_submodule_paths(&path),
+                path: path.clone(),
+            }));
+        } else if kind == RepositoryKind::Bare {
+            walked_directory.cached = Some(cached_directory);
+            return walked_directory;
+        }
+    }
+    // Subdirectories are too deep to contain a git repository to find
+    if options.max_depth.map_or(false, |max_depth| depth >= max_depth) {
+        walked_directory.cached = Some(cached_directory);
+        return walked_directory;
+    }
+    // A directory that has not been fully read is read again by the next scan
+    let mut is_cacheable = true;
+    if cached_directory.subdirectories.is_none() {
+        if let Err(error) = list_directory(&path,
+                                           &mut cached_directory,
+                                           &mut walked_directory.errors) {
+            walked_directory.errors.push(ScanError::new(&path, &error));
+            return walked_directory;
+        }
+        is_cacheable = walked_directory.errors.is_empty();
+    }
+    if cached_directory.ignore_file {
+        let ignore_file_path = path.join(IGNORE_FILE_NAME);
+        if let Some(rules) = ExcludeRules::from_file(&ignore_file_path) {
+            for error in rules.get_errors() {
+                walked_directory.errors
+                    .push(ScanError::invalid_pattern(&ignore_file_path, error));
+            }
+            ignore_files.push(Arc::new((path.clone(), rules)));
+        }
+    }
+    {
+        let directory_names = cached_directory.subdirectories.as_ref().unwrap().iter();
+        // Follow symbolic links to directories, unless they loop
+        let mut link_names = Vec::new();
+        for link_name in &cached_directory.links {
+            let link_path = path.join(link_name);
+            match fs::metadata(&link_path) {
+                Ok(metadata) => {
+                    if metadata.is_dir() {
+                        link_names.push(link_name);
+                    }
+                }
+                Err(error) => {
+                    if !is_excluded(&link_path, root, options, &ignore_files) {
+                        walked_directory.errors.push(match error.kind() {
+                            io::ErrorKind::NotFound => ScanError::broken_link(&link_path),
+                            _ => ScanError::new(&link_path, &error),
+                        });
+                    }
+                }
+            }
+        }
+        for name in directory_names.chain(link_names) {
+            let entry_path = path.join(name);
+            let skip_reason = if cached_directory.links.contains(name) &&
+                                 is_symlink_loop(&entry_path, &identity) {
+                Some(SkipReason::SymlinkLoop)
+            } else if options.skipped_mounts.contains(&entry_path) {
+                Some(SkipReason::MountPoint)
+            } else if is_excluded(&entry_path, root, options, &ignore_files) {
+                // The content of git directories is not worth reporting
+                if entry_path.file_name().map_or(false, |name| name == GIT_DIR_NAME) {
+                    continue;
+                }
+                Some(SkipReason::Excluded)
+            } else if !options.include_rules.may_contain(&entry_path) {
+                // Directories out of the include patterns are not worth reporting either
+                continue;
+            } else {
+                None
+            };
+            match skip_reason {
+                Some(reason) => {
+                    walked_directory.skipped.push(SkippedDirectory {
+                        path: entry_path.to_string_lossy().into_owned(),
+                        reason: reason,
+                    })
+                }
+                None => {
+                    walked_directory.subdirectories.push(PendingDirectory {
+                        path: entry_path,
+                        parent: parent.clone(),
+                        ignore_files: ignore_files.clone(),
+                        ancestors: identity.clone(),
+                        depth: depth + 1,
+                    })
+                }
+            }
+        }
+    }
+    if is_cacheable {
+        walked_directory.cached = Some(cached_directory);
+    }
+    walked_directory
+}
+
+///
+/// A directory being walked into by a thread of the scan: when it is dropped, even if the thread
+/// panicked, the thread is not counted as active anymore, so the other threads do not wait for
+/// it forever.
+///
+struct ActiveWalk<'a> {
+    state: &'a (Mutex<WalkState>, Condvar),
+}
+
+impl<'a> Drop for ActiveWalk<'a> {
+    fn drop(&mut self) {
+        let &(ref walk_state, ref condvar) = self.state;
+        let mut walk_state = walk_state.lock().unwrap_or_else(PoisonError::into_inner);
+        walk_state.active -= 1;
+        condvar.notify_all();
+    }
+}
+
+///
+/// Function to walk into directories of the shared state, until there is no more directory to
+/// walk into.
+///
+/// Each thread of the scan runs this function.
+///
+fn walk_directories(state: &(Mutex<WalkState>, Condvar),
+                    root: &Path,
+                    options: &Arc<ScanOptions>,
+                    cache: &ScanCache) {
+    let &(ref walk_state, ref condvar) = state;
+    loop {
+        let (directory, _active_walk) = {
+            let mut walk_state = walk_state.lock().unwrap();
+            while walk_state.pending.is_empty() && walk_state.active > 0 {
+                walk_state = condvar.wait(walk_state).unwrap();
+            }
+            match walk_state.pending.pop() {
+                Some(directory) => {
+                    walk_state.active += 1;
+                    (directory, ActiveWalk { state: state })
+                }
+                // Nothing to walk into, and nobody to find something else
+                None => return,
+            }
+        };
+        let directory_path = directory.path.clone();
+        let position = if options.keep_positions {
+            Some(WalkPosition {
+                directory: directory.clone(),
+                root: root.to_path_buf(),
+                options: options.clone(),
+            })
+        } else {
+            None
+        };
+        // A directory that makes the thread panic is reported, and the scan goes on
+        let walked_directory = panic::catch_unwind(AssertUnwindSafe(|| {
+            walk_directory(directory, root, options, cache)
+        }));
+        let mut walked_directory = match walked_directory {
+            Ok(walked_directory) => walked_directory,
+            Err(_) => {
+                let mut walk_state = walk_state.lock().unwrap();
+                walk_state.errors.push(ScanError::internal(&directory_path,
+                                                           "The scan of this directory stopped \
+                                                            unexpectedly"));
+                continue;
+            }
+        };
+        if let (&Some(ref progress), &Some(ref repository)) = (&options.progress,
+                                                               &walked_directory.found) {
+            progress.on_repository_found(repository);
+        }
+        let is_skipped = walked_directory.skipped
+            .iter()
+            .any(|skipped| Path::new(&skipped.path) == directory_path);
+        let mut walk_state = walk_state.lock().unwrap();
+        if !is_skipped {
+            walk_state.positions.extend(position);
+        }
+        walk_state.found.extend(walked_directory.found);
+        walk_state.pending.append(&mut walked_directory.subdirectories);
+        walk_state.skipped.append(&mut walked_directory.skipped);
+        walk_state.errors.append(&mut walked_directory.errors);
+        if let Some(cached_directory) = walked_directory.cached {
+            walk_state.cached
+                .push((directory_path.to_string_lossy().into_owned(), cached_directory));
         }
-        // If the DirEntry is not a directory, return false
-        return false;
     }
 }
 
 ///
 /// Function to scan all directories from a single directory.
 ///
-/// This function update a mutable vector of String, which represents each path for each git
-/// path for a given git repository.
+/// This function updates the report with each git repository found, and the scan cache with the
+/// directories walked into.
+/// Excluded directories are not walked into, neither are git repositories unless nested git
+/// repositories are requested.
+/// Directories are walked into by `options.jobs` threads.
 ///
-pub fn find_git_repositories(git_path: &mut Vec<String>, directory: &PathBuf) {
-    print!("Scanning repository from {:?} to find git repositories... (this can take a while) ",
-           directory);
-    io::stdout().flush().ok().expect("Could not flush stdout");
-    // Get all entries from the PathBuf given as parameter, filter and follow links
-    for entry in WalkDir::new(directory).follow_links(true).into_iter().filter_map(|e| e.ok()) {
-        if entry.is_git_repository() {
-            // Get the parent node
-            let entry_p = entry.path().parent().unwrap().clone();
-            // Puth the parent path name in the list of git repositories found
-            git_path.push(entry_p.to_str().unwrap().to_string());
+fn scan_root(root: &PathBuf,
+             options: &ScanOptions,
+             cache: &mut ScanCache,
+             report: &mut ScanReport) {
+    let state = Arc::new((Mutex::new(WalkState {
+                              pending: vec![PendingDirectory {
+                                                path: root.clone(),
+                                                parent: None,
+                                                ignore_files: Vec::new(),
+                                                ancestors: None,
+                                                depth: 0,
+                                            }],
+                              active: 0,
+                              found: Vec::new(),
+                              skipped: Vec::new(),
+                              errors: Vec::new(),
+                              cached: Vec::new(),
+                              positions: Vec::new(),
+                          }),
+                          Condvar::new()));
+    let shared_options = Arc::new(options.clone());
+    let previous_cache = Arc::new(mem::replace(cache, ScanCache::new()));
+    let workers = (1..options.jobs)
+        .map(|_| {
+            let state = state.clone();
+            let root = root.clone();
+            let options = shared_options.clone();
+            let previous_cache = previous_cache.clone();
+            thread::spawn(move || walk_directories(&state, &root, &options, &previous_cache))
+        })
+        .collect::<Vec<_>>();
+    walk_directories(&state, root, &shared_options, &previous_cache);
+    for worker in workers {
+        if worker.join().is_err() {
+            report.errors
+                .push(ScanError::internal(root, "A thread of the scan stopped unexpectedly"));
         }
     }
-    println!("Ok!");
+    // Directories of the scan root that have not been walked into are not cached anymore
+    *cache = Arc::try_unwrap(previous_cache).ok().expect("A scan thread is still running");
+    cache.directories.retain(|cached_path, _| !Path::new(cached_path).starts_with(root));
+    let mut walk_state = state.0.lock().unwrap_or_else(PoisonError::into_inner);
+    cache.directories.extend(walk_state.cached.drain(..));
+    report.repositories.append(&mut walk_state.found);
+    report.skipped.append(&mut walk_state.skipped);
+    report.errors.append(&mut walk_state.errors);
+    report.positions.append(&mut walk_state.positions);
 }
 
 ///
-/// Function to remove each hidden file from a String vector.
+/// Options used to match a path against a hidden directory pattern: wildcards never match a path
+/// separator.
 ///
-/// This function returns a vector of String references, which each reference represents a path repository.
+static HIDDEN_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
 ///
-pub fn filter_hidden_repositories<'a>(git_repositories: &'a Vec<String>) -> Vec<&'a String> {
-    git_repositories.iter()
-        .filter(|path_string| {
-            let current_path = Path::new(path_string);
-            !current_path.is_in_hidden_dir()
+/// The policy to keep, or not, git repositories found in hidden directories:
+/// `include_all`: If true, every git repository in a hidden directory is kept, unless it is
+/// denied - else, only allowed ones are kept
+/// `allowed`: The patterns of directories whose git repositories are kept
+/// `denied`: The patterns of directories whose git repositories are never kept
+/// `errors`: The messages of the invalid patterns, that are ignored
+///
+/// A pattern that contains a `/` matches a whole path (a leading `~` is the user home directory),
+/// else it matches a directory name.
+///
+#[derive(Clone)]
+pub struct HiddenPolicy {
+    include_all: bool,
+    allowed: Vec<Pattern>,
+    denied: Vec<Pattern>,
+    errors: Vec<String>,
+}
+
+impl HiddenPolicy {
+    ///
+    /// The function to instanciate a new HiddenPolicy structure, from lists of allowed and denied
+    /// patterns
+    ///
+    pub fn new(allowed: &[String], denied: &[String]) -> Self {
+        let mut errors = Vec::new();
+        HiddenPolicy {
+            include_all: false,
+            allowed: parse_hidden_patterns(allowed, &mut errors),
+            denied: parse_hidden_patterns(denied, &mut errors),
+            errors: errors,
+        }
+    }
+
+    ///
+    /// This method returns the messages of the invalid patterns, that are ignored
+    ///
+    pub fn get_errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    ///
+    /// A method to keep every git repository in a hidden directory, unless it is denied
+    ///
+    pub fn include_all(mut self, include_all: bool) -> Self {
+        self.include_all = include_all;
+        self
+    }
+
+    ///
+    /// This method returns a boolean - true if the git repository must be kept, else false
+    ///
+    pub fn is_allowed(&self, repository: &Path) -> bool {
+        if !repository.is_in_hidden_dir() {
+            return true;
+        }
+        if matches_hidden_patterns(&self.denied, repository) {
+            return false;
+        }
+        self.include_all || matches_hidden_patterns(&self.allowed, repository)
+    }
+}
+
+///
+/// Function to parse hidden directory patterns, as written in the configuration file.
+///
+/// The messages of the invalid patterns are added to `errors`.
+///
+fn parse_hidden_patterns(patterns: &[String], errors: &mut Vec<String>) -> Vec<Pattern> {
+    patterns.iter()
+        .filter_map(|pattern| {
+            let mut pattern = if pattern.contains('/') {
+                resolve_pattern(pattern)
+            } else {
+                pattern.clone()
+            };
+            while pattern.len() > 1 && pattern.ends_with('/') {
+                pattern.pop();
+            }
+            match Pattern::new(&pattern) {
+                Ok(pattern) => Some(pattern),
+                Err(error) => {
+                    errors.push(format!("Invalid hidden directory pattern '{}': {}",
+                                        pattern,
+                                        error));
+                    None
+                }
+            }
         })
-        .collect::<Vec<&String>>()
+        .collect()
+}
+
+///
+/// Function to know if a git repository, or one of the directories that contain it, matches a
+/// hidden directory pattern.
+///
+fn matches_hidden_patterns(patterns: &[Pattern], repository: &Path) -> bool {
+    let mut directory = Some(repository);
+    while let Some(current_directory) = directory {
+        let is_matching = patterns.iter().any(|pattern| if pattern.as_str().contains('/') {
+            pattern.matches_path_with(current_directory, &HIDDEN_MATCH_OPTIONS)
+        } else {
+            current_directory.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| pattern.matches_with(name, &HIDDEN_MATCH_OPTIONS))
+        });
+        if is_matching {
+            return true;
+        }
+        directory = current_directory.parent();
+    }
+    false
+}
+
+///
+/// Function to remove each git repository in a hidden directory from a FoundRepository vector,
+/// unless the hidden policy keeps it.
+///
+/// This function returns a vector of FoundRepository references, which each reference represents a path repository.
+///
+pub fn filter_hidden_repositories<'a>(git_repositories: &'a Vec<FoundRepository>,
+                                      hidden_policy: &HiddenPolicy)
+                                      -> Vec<&'a FoundRepository> {
+    git_repositories.iter()
+        .filter(|repository| hidden_policy.is_allowed(Path::new(&repository.path)))
+        .collect::<Vec<&FoundRepository>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scanner;
+    use exclude::ExcludeRules;
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn get_temp_dir(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("gyro-scan-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::canonicalize(&directory).unwrap()
+    }
+
+    #[test]
+    fn parallel_scan_finds_the_same_repositories() {
+        let root = get_temp_dir("parallel");
+        for first in 0..6 {
+            for second in 0..4 {
+                let directory = root.join(format!("group-{}", first))
+                    .join(format!("project-{}", second));
+                if (first + second) % 3 == 0 {
+                    fs::create_dir_all(directory.join(".git")).unwrap();
+                    fs::create_dir_all(directory.join("nested/.git")).unwrap();
+                } else {
+                    fs::create_dir_all(directory.join("src/deep/module/.git")).unwrap();
+                }
+            }
+        }
+        symlink(root.join("group-1"), root.join("group-0/link")).unwrap();
+        symlink(&root, root.join("group-2/loop")).unwrap();
+
+        let single_thread_report = Scanner::new().root(root.clone()).jobs(1).nested(true).scan();
+        let parallel_report = Scanner::new().root(root.clone()).jobs(4).nested(true).scan();
+        assert_eq!(single_thread_report.repositories.len(), 6 * 4 * 2 - 16);
+        assert_eq!(single_thread_report.repositories, parallel_report.repositories);
+        assert!(single_thread_report.repositories.iter().any(|repository| {
+            !repository.aliases.is_empty()
+        }));
+        assert!(parallel_report.errors.is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn kept_positions_are_the_directories_walked_into() {
+        let root = get_temp_dir("positions");
+        fs::create_dir_all(root.join("api/.git/refs")).unwrap();
+        fs::create_dir_all(root.join("code/web")).unwrap();
+        fs::create_dir_all(root.join("target/build")).unwrap();
+
+        let report = Scanner::new()
+            .root(root.clone())
+            .exclude(ExcludeRules::new(&[String::from("target")]))
+            .jobs(2)
+            .keep_positions(true)
+            .scan();
+        let paths = report.positions
+            .iter()
+            .map(|position| position.get_path().to_path_buf())
+            .collect::<Vec<PathBuf>>();
+        assert_eq!(paths,
+                   vec![root.clone(), root.join("api"), root.join("code"), root.join("code/web")]);
+        assert!(report.positions[0].is_root());
+        assert!(!report.positions[1].is_root());
+        let step = report.positions[2].walk();
+        assert_eq!(step.subdirectories.len(), 1);
+        assert_eq!(step.subdirectories[0].get_path(), root.join("code/web").as_path());
+
+        let report = Scanner::new().root(root.clone()).scan();
+        assert!(report.positions.is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,395 @@
+use {GIT_DIR_NAME, GIT_HEAD_FILE_NAME, GIT_OBJECTS_DIR_NAME, GIT_REFS_DIR_NAME};
+use scan::{FoundRepository, ScanError, ScanReport, WalkPosition};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::mem;
+use std::os::raw::{c_char, c_int};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+extern "C" {
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int;
+    fn inotify_rm_watch(fd: c_int, wd: c_int) -> c_int;
+}
+
+///
+/// The header of an inotify event, followed by `len` bytes of file name
+///
+#[repr(C)]
+struct InotifyEvent {
+    wd: c_int,
+    mask: u32,
+    cookie: u32,
+    len: u32,
+}
+
+/// Flag of inotify_init1, to close the inotify file descriptor when executing another program
+const IN_CLOEXEC: c_int = 0o2000000;
+
+/// A file or a directory has been created in a watched directory
+const IN_CREATE: u32 = 0x00000100;
+/// A file or a directory has been deleted from a watched directory
+const IN_DELETE: u32 = 0x00000200;
+/// A file or a directory has been moved out of a watched directory
+const IN_MOVED_FROM: u32 = 0x00000040;
+/// A file or a directory has been moved into a watched directory
+const IN_MOVED_TO: u32 = 0x00000080;
+/// The watch has been removed, because the directory has been deleted or unwatched
+const IN_IGNORED: u32 = 0x00008000;
+/// Events have been lost, because the event queue overflowed
+const IN_Q_OVERFLOW: u32 = 0x00004000;
+/// The subject of the event is a directory
+const IN_ISDIR: u32 = 0x40000000;
+/// Only watch the path if it is a directory
+const IN_ONLYDIR: u32 = 0x01000000;
+/// Do not follow the path if it is a symbolic link
+const IN_DONT_FOLLOW: u32 = 0x02000000;
+
+/// Error of inotify_add_watch, when the maximal number of watches has been reached
+const ENOSPC: i32 = 28;
+
+///
+/// Size of the buffer used to read inotify events
+///
+const EVENTS_BUFFER_SIZE: usize = 64 * 1024;
+
+///
+/// A change of the git repositories in the watched directories:
+/// `Created` is corresponding to a new git repository (a clone, `git init`, or a directory moved
+/// into a watched directory)
+/// `Removed` is corresponding to the local path of a git repository that is not on the disk
+/// anymore (deleted, moved out of a watched directory, or not a git repository anymore)
+///
+#[derive(Clone, Debug)]
+pub enum WatchEvent {
+    Created(FoundRepository),
+    Removed(String),
+}
+
+///
+/// A watcher of the git repositories in the root directories of a scan, based on inotify:
+/// `inotify`: The inotify file descriptor
+/// `watches`: The position of the watched directories, by watch descriptor
+/// `paths`: The watch descriptor of the watched directories, by path - sorted, so the watched
+/// directories of a tree follow each other
+/// `repositories`: The local paths of the git repositories in the watched directories
+/// `errors`: The errors encountered since the last call to `take_errors`
+///
+/// The directories the scan walked into are watched, and new directories are walked into with
+/// the options of the scan (exclusion patterns, .gyroignore files, depths, include patterns,
+/// mount points...), so only the git repositories the scan would find are reported.
+/// Symbolic links are never followed.
+///
+pub struct RepositoryWatcher {
+    inotify: File,
+    watches: HashMap<c_int, WalkPosition>,
+    paths: BTreeMap<PathBuf, c_int>,
+    repositories: BTreeSet<PathBuf>,
+    errors: Vec<ScanError>,
+}
+
+impl RepositoryWatcher {
+    ///
+    /// The function to instanciate a new RepositoryWatcher structure, that watches the directories
+    /// walked into by a scan, with the options of the scan - the scan must have kept their
+    /// positions (`Scanner::keep_positions`)
+    ///
+    /// This function returns a Result type, that contains the watcher, or the error of inotify
+    ///
+    pub fn new(report: &ScanReport) -> io::Result<Self> {
+        let fd = unsafe { inotify_init1(IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut watcher = RepositoryWatcher {
+            inotify: unsafe { File::from_raw_fd(fd) },
+            watches: HashMap::new(),
+            paths: BTreeMap::new(),
+            repositories: BTreeSet::new(),
+            errors: Vec::new(),
+        };
+        // Positions are sorted by path, so a directory comes after its parent directory, and it
+        // is only watched if its parent directory is - symbolic links are not followed
+        for position in &report.positions {
+            let path = position.get_path();
+            let is_reachable = position.is_root() ||
+                               path.parent().map_or(false, |parent| watcher.is_watched(parent));
+            if !is_reachable || !is_real_directory(path) {
+                continue;
+            }
+            if let Err(error) = watcher.add_watch(position) {
+                watcher.errors.push(ScanError::new(path, &error));
+            }
+        }
+        // Git repositories that are already there are not reported
+        for repository in &report.repositories {
+            for path in Some(&repository.path).into_iter().chain(repository.aliases.iter()) {
+                let path = PathBuf::from(path);
+                if watcher.is_watched(&path) {
+                    watcher.repositories.insert(path);
+                }
+            }
+        }
+        Ok(watcher)
+    }
+
+    ///
+    /// This method returns the errors encountered since its last call, like directories that
+    /// cannot be watched
+    ///
+    pub fn take_errors(&mut self) -> Vec<ScanError> {
+        mem::replace(&mut self.errors, Vec::new())
+    }
+
+    ///
+    /// This method waits for changes in the watched directories, and returns the changes of git
+    /// repositories they made - possibly none
+    ///
+    pub fn read_events(&mut self) -> io::Result<Vec<WatchEvent>> {
+        let mut buffer = vec![0u8; EVENTS_BUFFER_SIZE];
+        let length = self.inotify.read(&mut buffer)?;
+        let mut events = Vec::new();
+        let mut offset = 0;
+        while offset + mem::size_of::<InotifyEvent>() <= length {
+            let event = unsafe {
+                ptr::read_unaligned(buffer[offset..].as_ptr() as *const InotifyEvent)
+            };
+            let name_start = offset + mem::size_of::<InotifyEvent>();
+            let name_end = name_start + event.len as usize;
+            let name = buffer[name_start..name_end]
+                .split(|byte| *byte == 0)
+                .next()
+                .unwrap_or(&[]);
+            let name = Path::new(OsStr::from_bytes(name)).to_path_buf();
+            self.handle_event(&event, &name, &mut events);
+            offset = name_end;
+        }
+        Ok(events)
+    }
+
+    ///
+    /// Method to update the watches and the known git repositories after an inotify event, and
+    /// to collect the changes of git repositories
+    ///
+    fn handle_event(&mut self, event: &InotifyEvent, name: &Path, events: &mut Vec<WatchEvent>) {
+        if event.mask & IN_Q_OVERFLOW != 0 {
+            self.errors.push(ScanError::new(Path::new(""),
+                                            &io::Error::new(io::ErrorKind::Other,
+                                                            "Too many changes, some of them \
+                                                             have been lost")));
+            return;
+        }
+        if event.mask & IN_IGNORED != 0 {
+            self.remove_watch(event.wd);
+            return;
+        }
+        let position = match self.watches.get(&event.wd) {
+            Some(position) => position.clone(),
+            None => return,
+        };
+        let path = position.get_path().join(name);
+        // Files and directories that make a git repository - or a bare one - and new directories
+        let git_files = [GIT_DIR_NAME, GIT_HEAD_FILE_NAME, GIT_OBJECTS_DIR_NAME, GIT_REFS_DIR_NAME];
+        let is_git_file = git_files.iter()
+            .any(|git_file| name == Path::new(git_file));
+        let is_directory = event.mask & IN_ISDIR != 0;
+        if is_git_file || (is_directory && event.mask & (IN_CREATE | IN_MOVED_TO) != 0) {
+            self.refresh_directory(&position, events);
+        } else if is_directory && event.mask & (IN_DELETE | IN_MOVED_FROM) != 0 {
+            self.unwatch_tree(&path, true, events);
+        }
+    }
+
+    ///
+    /// Method to add a git repository to the known ones, or to remove it, after walking into its
+    /// directory
+    ///
+    fn update_repository(&mut self,
+                         directory: &Path,
+                         found: Option<FoundRepository>,
+                         events: &mut Vec<WatchEvent>) {
+        let is_known = self.repositories.contains(directory);
+        match found {
+            Some(repository) => {
+                if !is_known {
+                    self.repositories.insert(directory.to_path_buf());
+                    events.push(WatchEvent::Created(repository));
+                }
+            }
+            None => {
+                if is_known {
+                    self.repositories.remove(directory);
+                    events.push(WatchEvent::Removed(directory.to_string_lossy().into_owned()));
+                }
+            }
+        }
+    }
+
+    ///
+    /// Method to walk again into a watched directory, after a change: the git repository it may
+    /// be is updated, the subdirectories to walk into are watched, and the other ones are not
+    /// watched anymore (like the content of a new git repository)
+    ///
+    fn refresh_directory(&mut self, position: &WalkPosition, events: &mut Vec<WatchEvent>) {
+        let directory = position.get_path().to_path_buf();
+        // A directory being deleted is unwatched by the event of its deletion
+        if !is_real_directory(&directory) {
+            return;
+        }
+        let step = position.walk();
+        self.errors.extend(step.errors);
+        self.update_repository(&directory, step.found, events);
+        let subdirectories = step.subdirectories
+            .into_iter()
+            .filter(|subdirectory| is_real_directory(subdirectory.get_path()))
+            .collect::<Vec<WalkPosition>>();
+        let subdirectory_paths = subdirectories.iter()
+            .map(|subdirectory| subdirectory.get_path().to_path_buf())
+            .collect::<HashSet<PathBuf>>();
+        // A deleted subdirectory is unwatched by the event of its deletion
+        let unwatched_subdirectories = fs::read_dir(&directory)
+            .map(|entries| {
+                entries.filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| self.is_watched(path) && !subdirectory_paths.contains(path))
+                    .collect::<Vec<PathBuf>>()
+            })
+            .unwrap_or_default();
+        for subdirectory in unwatched_subdirectories {
+            self.unwatch_tree(&subdirectory, true, events);
+        }
+        for subdirectory in subdirectories {
+            if !self.is_watched(subdirectory.get_path()) {
+                self.watch_tree(subdirectory, events);
+            }
+        }
+    }
+
+    ///
+    /// This method returns a boolean - true if the directory is watched, else false
+    ///
+    fn is_watched(&self, directory: &Path) -> bool {
+        self.paths.contains_key(directory)
+    }
+
+    ///
+    /// Method to watch a directory and the subdirectories to walk into, and to collect the git
+    /// repositories in them
+    ///
+    fn watch_tree(&mut self, position: WalkPosition, events: &mut Vec<WatchEvent>) {
+        if !is_real_directory(position.get_path()) {
+            return;
+        }
+        // The watch is added before walking into the directory, to not miss a change
+        let wd = match self.add_watch(&position) {
+            Ok(wd) => wd,
+            Err(error) => {
+                self.errors.push(ScanError::new(position.get_path(), &error));
+                return;
+            }
+        };
+        let step = position.walk();
+        if step.is_skipped {
+            unsafe { inotify_rm_watch(self.inotify_fd(), wd) };
+            self.remove_watch(wd);
+            return;
+        }
+        self.errors.extend(step.errors);
+        self.update_repository(position.get_path(), step.found, events);
+        for subdirectory in step.subdirectories {
+            self.watch_tree(subdirectory, events);
+        }
+    }
+
+    ///
+    /// Method to stop watching the subdirectories of a directory (and the directory itself, if
+    /// `with_directory` is true), and to remove the git repositories in them
+    ///
+    fn unwatch_tree(&mut self,
+                    directory: &Path,
+                    with_directory: bool,
+                    events: &mut Vec<WatchEvent>) {
+        // The paths of a tree follow the path of its directory, in sorted collections
+        let is_unwatched = |path: &Path| with_directory || path != directory;
+        let watch_descriptors = self.paths
+            .range(directory.to_path_buf()..)
+            .take_while(|&(path, _)| path.starts_with(directory))
+            .filter(|&(path, _)| is_unwatched(path))
+            .map(|(_, wd)| *wd)
+            .collect::<Vec<c_int>>();
+        for wd in watch_descriptors {
+            // The watch of a deleted directory is already removed
+            unsafe { inotify_rm_watch(self.inotify_fd(), wd) };
+            self.remove_watch(wd);
+        }
+        let repositories = self.repositories
+            .range(directory.to_path_buf()..)
+            .take_while(|path| path.starts_with(directory))
+            .filter(|path| is_unwatched(path))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        for repository in repositories {
+            self.repositories.remove(&repository);
+            events.push(WatchEvent::Removed(repository.to_string_lossy().into_owned()));
+        }
+    }
+
+    ///
+    /// Method to add an inotify watch on a directory, and to return its watch descriptor
+    ///
+    fn add_watch(&mut self, position: &WalkPosition) -> io::Result<c_int> {
+        let path = CString::new(position.get_path().as_os_str().as_bytes())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        let wd = unsafe {
+            inotify_add_watch(self.inotify_fd(),
+                              path.as_ptr(),
+                              IN_CREATE | IN_DELETE | IN_MOVED_FROM | IN_MOVED_TO | IN_ONLYDIR |
+                              IN_DONT_FOLLOW)
+        };
+        if wd < 0 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() == Some(ENOSPC) {
+                return Err(io::Error::new(io::ErrorKind::Other,
+                                          "Too many watched directories - exclude some of them, \
+                                           or raise fs.inotify.max_user_watches"));
+            }
+            return Err(error);
+        }
+        // A directory watched again, through another path, gets the same watch descriptor
+        if let Some(previous_position) = self.watches.insert(wd, position.clone()) {
+            self.paths.remove(previous_position.get_path());
+        }
+        self.paths.insert(position.get_path().to_path_buf(), wd);
+        Ok(wd)
+    }
+
+    ///
+    /// Method to forget a watch descriptor, once its inotify watch has been removed
+    ///
+    fn remove_watch(&mut self, wd: c_int) {
+        if let Some(position) = self.watches.remove(&wd) {
+            self.paths.remove(position.get_path());
+        }
+    }
+
+    ///
+    /// This method returns the inotify file descriptor
+    ///
+    fn inotify_fd(&self) -> c_int {
+        self.inotify.as_raw_fd()
+    }
+}
+
+///
+/// Function to know if a path is a directory, and not a symbolic link to a directory.
+///
+fn is_real_directory(path: &Path) -> bool {
+    fs::symlink_metadata(path).map(|metadata| metadata.is_dir()).unwrap_or(false)
+}